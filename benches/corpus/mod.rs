@@ -0,0 +1,27 @@
+//! Synthetic consent strings for the benchmarks. Every corpus comes from a fixed seed, so runs
+//! are comparable across machines and need no network access.
+
+#![allow(dead_code)]
+
+use gdpr_consent_string::generate::Config;
+use gdpr_consent_string::{Generator, VendorEncoding};
+
+// 25 May 2018, when the GDPR came into force
+const SEED: u64 = 20180525;
+
+/// `count` strings whose vendor consents are all written with `encoding`, each with exactly
+/// `vendors` vendors of which about nine in ten have consent.
+pub fn strings(encoding: VendorEncoding, vendors: u16, count: usize) -> Vec<String> {
+    let config = Config {
+        max_vendor_ids: vendors..=vendors,
+        consent_density: 0.9,
+        encoding,
+        ..Config::default()
+    };
+    Generator::new(config, SEED).unwrap().take(count).collect()
+}
+
+/// A mix of strings like those seen in bid requests
+pub fn mixed(count: usize) -> Vec<String> {
+    Generator::new(Config::default(), SEED).unwrap().take(count).collect()
+}
@@ -0,0 +1,41 @@
+#[macro_use]
+extern crate criterion;
+extern crate gdpr_consent_string;
+
+mod corpus;
+
+use criterion::{Benchmark, Criterion, Throughput};
+use gdpr_consent_string::filter::Filter;
+use gdpr_consent_string::ConsentString;
+
+const FILTER: &str =
+    "(consents has 755 & purposes has 1 & cmpid in [7, 10, 24]) | consentlanguage like \"f*\"";
+
+fn tree_walker(c: &mut Criterion) {
+    let gdpr = ConsentString::parse("BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA").unwrap();
+    let filter = Filter::compile(FILTER).unwrap();
+    c.bench_function("Expr::eval", move |b| b.iter(|| filter.expr().eval(&gdpr)));
+}
+
+fn compiled(c: &mut Criterion) {
+    let gdpr = ConsentString::parse("BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA").unwrap();
+    let filter = Filter::compile(FILTER).unwrap();
+    c.bench_function("Filter::matches", move |b| b.iter(|| filter.matches(&gdpr)));
+}
+
+fn over_corpus(c: &mut Criterion) {
+    let corpus: Vec<_> = corpus::mixed(1000)
+        .iter()
+        .map(|s| ConsentString::parse(s).unwrap())
+        .collect();
+    let filter = Filter::compile(FILTER).unwrap();
+    c.bench(
+        "corpus",
+        Benchmark::new("Filter::matches", move |b| {
+            b.iter(|| corpus.iter().filter(|gdpr| filter.matches(gdpr)).count())
+        }).throughput(Throughput::Elements(1000)),
+    );
+}
+
+criterion_group!(benches, tree_walker, compiled, over_corpus);
+criterion_main!(benches);
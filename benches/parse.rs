@@ -0,0 +1,32 @@
+#[macro_use]
+extern crate criterion;
+extern crate gdpr_consent_string;
+
+mod corpus;
+
+use criterion::{Criterion, ParameterizedBenchmark, Throughput};
+use gdpr_consent_string::{ConsentString, VendorEncoding};
+
+const BATCH: usize = 100;
+
+fn parse(c: &mut Criterion) {
+    let batch = |encoding, vendors| corpus::strings(encoding, vendors, BATCH);
+    c.bench(
+        "parse",
+        ParameterizedBenchmark::new(
+            "bitfield",
+            move |b, &vendors| {
+                let strings = batch(VendorEncoding::BitField, vendors);
+                b.iter(|| strings.iter().filter_map(|s| ConsentString::parse(s)).count())
+            },
+            vec![10, 100, 1000, 5000],
+        ).with_function("range", move |b, &vendors| {
+            let strings = batch(VendorEncoding::Range, vendors);
+            b.iter(|| strings.iter().filter_map(|s| ConsentString::parse(s)).count())
+        })
+            .throughput(|_| Throughput::Elements(BATCH as u32)),
+    );
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
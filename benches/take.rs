@@ -0,0 +1,23 @@
+//! Needs the `bench` feature: `cargo bench --features bench --bench take`
+
+#[macro_use]
+extern crate criterion;
+extern crate gdpr_consent_string;
+
+mod corpus;
+
+use criterion::Criterion;
+use gdpr_consent_string::{bench_take, VendorEncoding};
+
+fn take(c: &mut Criterion) {
+    // 6000 bits: a little more than a bitfield of the whole 2018 vendor list
+    let input = corpus::strings(VendorEncoding::BitField, 6000, 1).remove(0);
+    c.bench_function_over_inputs(
+        "BitDecoder::take",
+        move |b, &&width| b.iter(|| bench_take(&input, width)),
+        &[1, 6, 12, 16, 36],
+    );
+}
+
+criterion_group!(benches, take);
+criterion_main!(benches);
@@ -0,0 +1,24 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate gdpr_consent_string;
+
+use gdpr_consent_string::filter::Filter;
+use gdpr_consent_string::ConsentString;
+
+fuzz_target!(|data: &[u8]| {
+    let source = match ::std::str::from_utf8(data) {
+        Ok(source) => source,
+        Err(_) => return,
+    };
+    // Programs that include files are skipped: the fuzzer shouldn't read the filesystem
+    if source.contains("include") {
+        return;
+    }
+    if let Ok(filter) = Filter::compile(source) {
+        let gdpr = ConsentString::parse("BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA").unwrap();
+        assert_eq!(filter.matches(&gdpr), filter.expr().eval(&gdpr));
+        let printed = filter.to_string();
+        Filter::compile(&printed).expect("printed filters parse");
+    }
+});
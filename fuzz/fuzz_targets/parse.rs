@@ -0,0 +1,16 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate gdpr_consent_string;
+
+use gdpr_consent_string::{ConsentString, ParseOptions};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = ::std::str::from_utf8(data) {
+        if let Ok(consent_string) = ConsentString::try_parse(s) {
+            // Anything that parses must survive being re-encoded
+            consent_string.encode().unwrap();
+        }
+        let _ = ConsentString::parse_with(s, &ParseOptions::strict());
+    }
+});
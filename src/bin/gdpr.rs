@@ -0,0 +1,63 @@
+extern crate gdpr_consent_string;
+extern crate structopt;
+
+use gdpr_consent_string::cli;
+use structopt::StructOpt;
+
+/// Decode, encode, inspect and select IAB GDPR consent strings
+#[derive(StructOpt, Debug)]
+#[structopt(name = "gdpr")]
+enum Command {
+    /// Decode consent strings and print them in a choice of formats
+    #[structopt(name = "decode")]
+    Decode(cli::decode::Args),
+
+    /// Build consent strings from JSON or from field options
+    #[structopt(name = "encode")]
+    Encode(cli::encode::Args),
+
+    /// Select consent strings matching a filter expression
+    #[structopt(name = "filter")]
+    #[cfg(feature = "filter")]
+    Filter(cli::filter::Args),
+
+    /// Print random but valid consent strings
+    #[structopt(name = "generate")]
+    #[cfg(feature = "generate")]
+    Generate(cli::generate::Args),
+
+    /// Check that consent strings can be decoded
+    #[structopt(name = "validate")]
+    Validate(cli::validate::Args),
+
+    /// Show how two consent strings differ, field by field
+    #[structopt(name = "diff")]
+    Diff {
+        #[structopt(name = "A")]
+        a: String,
+        #[structopt(name = "B")]
+        b: String,
+    },
+
+    /// Print aggregate statistics over many consent strings
+    #[structopt(name = "stats")]
+    Stats(cli::stats::Args),
+}
+
+fn main() {
+    match Command::from_args() {
+        Command::Decode(args) => cli::decode::run(args.string, args.options, &args.source),
+        Command::Encode(args) => cli::encode::run(args),
+        #[cfg(feature = "filter")]
+        Command::Filter(args) => {
+            cli::filter::run(args.options, args.program.as_deref(), &args.source)
+        }
+        #[cfg(feature = "generate")]
+        Command::Generate(args) => cli::generate::run(args),
+        Command::Validate(args) => {
+            std::process::exit(cli::validate::run(args.string, args.strict, &args.source))
+        }
+        Command::Diff { a, b } => std::process::exit(cli::decode::diff(&a, &b)),
+        Command::Stats(args) => cli::stats::run(&args.source),
+    }
+}
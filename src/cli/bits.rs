@@ -0,0 +1,256 @@
+use {ranges, sextet, time};
+
+/// Walks the bits of a consent string field by field, recording one annotated line per field.
+struct Dump {
+    bits: Vec<bool>,
+    pos: usize,
+    lines: Vec<String>,
+}
+
+impl Dump {
+    /// Reads a `width`-bit field and describes it with `describe`. If the string ends first, the
+    /// bits that remain are reported as truncated and `None` is returned.
+    fn field<F: FnOnce(u64) -> String>(
+        &mut self,
+        name: &str,
+        width: usize,
+        describe: F,
+    ) -> Option<u64> {
+        let start = self.pos;
+        let end = self.bits.len().min(start + width);
+        let raw = &self.bits[start..end];
+        self.pos = end;
+        if raw.len() < width {
+            self.lines.push(line(
+                start,
+                width,
+                name,
+                raw,
+                &format!("! truncated: {} of {} bits present", raw.len(), width),
+            ));
+            return None;
+        }
+        let value = raw.iter().fold(0, |acc, &bit| acc << 1 | bit as u64);
+        let description = describe(value);
+        self.lines.push(line(start, width, name, raw, &description));
+        Some(value)
+    }
+}
+
+fn line(offset: usize, width: usize, name: &str, raw: &[bool], value: &str) -> String {
+    let raw: String = raw.iter().map(|&bit| if bit { '1' } else { '0' }).collect();
+    format!("{:>5} {:>3}  {:<20} {:<36}  {}", offset, width, name, raw, value)
+        .trim_end()
+        .to_string()
+}
+
+/// Prints every field of a consent string with its bit offset, width, raw bits and decoded value.
+///
+/// Unlike `ConsentString::parse`, this does not stop at the first problem: truncated fields, range
+/// entries beyond the maximum vendor id and bits left over after the vendor section are all marked
+/// with `!`.
+pub fn dump(consent_str: &str) -> Result<String, String> {
+    let mut bits = Vec::with_capacity(consent_str.len() * 6);
+    for (position, c) in consent_str.char_indices() {
+        let sextet = sextet(c).ok_or_else(|| {
+            format!(
+                "Character {:?} at position {} is not a valid Base64 character",
+                c, position
+            )
+        })?;
+        bits.extend((0..6).rev().map(|bit| sextet >> bit & 1 == 1));
+    }
+    let mut dump = Dump {
+        bits,
+        pos: 0,
+        lines: vec![format!(
+            "{:>5} {:>3}  {:<20} {:<36}  {}",
+            "bit", "len", "field", "raw", "value"
+        )],
+    };
+    fields(&mut dump);
+    let leftover = dump.bits.len() - dump.pos;
+    if leftover > 0 {
+        let start = dump.pos;
+        let raw = &dump.bits[start..];
+        // Encoders pad to a whole number of bytes and then to a whole number of characters
+        let padded = (dump.pos.div_ceil(8) * 8).div_ceil(6) * 6;
+        let value = if dump.bits.len() <= padded && raw.iter().all(|&bit| !bit) {
+            "padding".to_string()
+        } else {
+            format!("! {} bits ignored by the parser", leftover)
+        };
+        for (idx, chunk) in raw.chunks(32).enumerate() {
+            let label = if idx == 0 { "trailing bits" } else { "" };
+            let value = if idx == 0 { value.as_str() } else { "" };
+            dump.lines
+                .push(line(start + idx * 32, chunk.len(), label, chunk, value));
+        }
+    }
+    Ok(dump.lines.join("\n"))
+}
+
+/// Reads the fields in order, stopping at the first truncated one.
+fn fields(dump: &mut Dump) -> Option<()> {
+    dump.field("version", 6, |v| v.to_string())?;
+    dump.field("created", 36, timestamp)?;
+    dump.field("last updated", 36, timestamp)?;
+    dump.field("cmp id", 12, |v| v.to_string())?;
+    dump.field("cmp version", 12, |v| v.to_string())?;
+    dump.field("consent screen", 6, |v| v.to_string())?;
+    dump.field("consent language", 12, |v| {
+        let letters = [v >> 6, v & 0x3f];
+        if letters.iter().all(|&letter| letter < 26) {
+            letters
+                .iter()
+                .map(|&letter| (b'a' + letter as u8) as char)
+                .collect()
+        } else {
+            format!("! letters {} and {} are not both in a-z", letters[0], letters[1])
+        }
+    })?;
+    dump.field("vendor list version", 12, |v| v.to_string())?;
+    dump.field("purposes allowed", 24, |v| {
+        let ids: Vec<_> = (1..=24).filter(|id| v >> (24 - id) & 1 == 1).collect();
+        let purposes = if ids.is_empty() {
+            "none".to_string()
+        } else {
+            ranges(ids.iter().cloned())
+        };
+        if ids.iter().any(|&id| id > 5) {
+            format!("{} ! undefined purposes set", purposes)
+        } else {
+            purposes
+        }
+    })?;
+    let max_vendor_id = dump.field("max vendor id", 16, |v| v.to_string())?;
+    let range = dump.field("encoding type", 1, |v| {
+        if v == 1 { "range" } else { "bitfield" }.to_string()
+    })?;
+    if range == 0 {
+        let mut id = 1;
+        while id <= max_vendor_id {
+            let width = (max_vendor_id - id + 1).min(32);
+            let first = id;
+            dump.field(
+                &format!("vendors {}-{}", first, first + width - 1),
+                width as usize,
+                |v| {
+                    let consented: Vec<_> = (0..width)
+                        .filter(|bit| v >> (width - 1 - bit) & 1 == 1)
+                        .map(|bit| (first + bit) as usize)
+                        .collect();
+                    if consented.is_empty() {
+                        "none".to_string()
+                    } else {
+                        ranges(consented)
+                    }
+                },
+            )?;
+            id += width;
+        }
+    } else {
+        dump.field("default consent", 1, |v| {
+            if v == 1 { "consent" } else { "no consent" }.to_string()
+        })?;
+        let entries = dump.field("number of entries", 12, |v| v.to_string())?;
+        let check = |id: u64| {
+            if id == 0 || id > max_vendor_id {
+                format!("{} ! outside 1-{}", id, max_vendor_id)
+            } else {
+                id.to_string()
+            }
+        };
+        for entry in 1..=entries {
+            let is_range = dump.field(&format!("entry {}", entry), 1, |v| {
+                if v == 1 { "range" } else { "single" }.to_string()
+            })?;
+            if is_range == 1 {
+                let start = dump.field("  start vendor id", 16, check)?;
+                dump.field("  end vendor id", 16, |end| {
+                    if end < start {
+                        format!("{} ! before start {}", end, start)
+                    } else {
+                        check(end)
+                    }
+                })?;
+            } else {
+                dump.field("  vendor id", 16, check)?;
+            }
+        }
+    }
+    Some(())
+}
+
+fn timestamp(deciseconds: u64) -> String {
+    time(deciseconds)
+        .map(|time| time.to_string())
+        .unwrap_or_else(|| format!("! {} is out of range", deciseconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CANONICAL: &str = "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA";
+
+    /// Encodes fields given as `(value, width)` pairs, padded with zeros to whole characters
+    fn encode(fields: &[(u64, usize)]) -> String {
+        let mut bits: Vec<bool> = fields
+            .iter()
+            .flat_map(|&(value, width)| (0..width).rev().map(move |bit| value >> bit & 1 == 1))
+            .collect();
+        while !bits.len().is_multiple_of(6) {
+            bits.push(false);
+        }
+        let alphabet = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+        bits.chunks(6)
+            .map(|sextet| {
+                alphabet[sextet.iter().fold(0, |acc, &bit| acc << 1 | bit as usize)] as char
+            })
+            .collect()
+    }
+
+    #[test]
+    fn canonical() {
+        let text = dump(CANONICAL).unwrap();
+        assert!(!text.contains('!'));
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(
+            lines[1],
+            "    0   6  version              000001                                1"
+        );
+        assert_eq!(
+            lines[lines.len() - 1],
+            "  203   7  trailing bits        0000000                               padding"
+        );
+    }
+
+    #[test]
+    fn trailing_data() {
+        let text = dump(&format!("{}BBBB", CANONICAL)).unwrap();
+        let last = text.lines().last().unwrap();
+        assert!(last.ends_with("! 31 bits ignored by the parser"));
+    }
+
+    #[test]
+    fn entry_above_max_vendor_id() {
+        let mut fields = vec![(1, 6), (0, 36), (0, 36), (7, 12), (1, 12), (3, 6)];
+        fields.extend(&[(4 << 6 | 13, 12), (8, 12), (0b111 << 21, 24), (10, 16)]);
+        // Range encoding, no default consent, and a single entry for vendor 20
+        fields.extend(&[(1, 1), (0, 1), (1, 12), (0, 1), (20, 16)]);
+        let text = dump(&encode(&fields)).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert!(lines[lines.len() - 2].ends_with("20 ! outside 1-10"));
+    }
+
+    #[test]
+    fn truncated() {
+        let text = dump(&CANONICAL[..10]).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[3].starts_with("   42  36  last updated         001110000100000101 "));
+        assert!(lines[3].ends_with("! truncated: 18 of 36 bits present"));
+        assert!(dump("BOE*").is_err());
+    }
+}
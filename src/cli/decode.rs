@@ -0,0 +1,316 @@
+use cli::bits;
+use cli::input::{OnError, Source};
+use cli::output::{join_cells, Column};
+use cli::{fail, load_vendor_list};
+use field::FIELDS;
+use serde_json;
+use std::cmp;
+use std::path::PathBuf;
+use {deciseconds, ranges, Change, ConsentString, Field, Purposes, VendorList};
+
+#[derive(StructOpt, Debug)]
+pub struct Options {
+    /// Output format: human, json, csv, tsv or bits
+    #[structopt(short = "o", long = "output")]
+    format: Option<String>,
+
+    /// In human output, list vendor consents as ranges of ids with totals instead of a chart
+    #[structopt(long = "compact")]
+    compact: bool,
+
+    /// Global Vendor List JSON, used to name vendors in --compact output
+    #[structopt(long = "gvl", parse(from_os_str))]
+    gvl: Option<PathBuf>,
+
+    /// Comma-separated columns for csv and tsv output, named as in filter expressions, such as
+    /// `cmpid,consents`; defaults to every field
+    #[structopt(long = "columns")]
+    columns: Option<String>,
+}
+
+/// Arguments to `gdpr decode`
+#[derive(StructOpt, Debug)]
+pub struct Args {
+    #[structopt(name = "STRING")]
+    pub string: Option<String>,
+
+    #[structopt(flatten)]
+    pub options: Options,
+
+    #[structopt(flatten)]
+    pub source: Source,
+}
+
+/// The JSON shape of a consent string, as printed by `decode -o json` and read by `encode`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SConsentString {
+    pub version: u8,
+    pub created: u64,
+    pub last_updated: u64,
+    pub cmp_id: u16,
+    pub cmp_version: u16,
+    pub consent_screen: u8,
+    pub consent_language: String,
+    pub vendor_list_version: u16,
+    pub purposes_allowed: Vec<u8>,
+    pub max_vendor_id: u16,
+    pub vendor_consents: Vec<u16>,
+}
+
+#[derive(Debug)]
+enum Format {
+    /// Without a chart, vendor consents are listed as ranges, named from the vendor list if given
+    Human {
+        chart: bool,
+        names: Option<VendorList>,
+    },
+    Json,
+    /// One row per string with the given columns, separated by `,` (quoted as CSV) or tab
+    Table(Vec<Column>, char),
+    /// Every field with its bit offset, width and raw bits
+    Bits,
+}
+
+/// Decodes the given string, or every string in the input, printing each one.
+pub fn run(string: Option<String>, options: Options, source: &Source) {
+    let columns = match options.columns {
+        Some(columns) => columns
+            .split(',')
+            .map(|column| column.parse())
+            .collect::<Result<Vec<Column>, _>>()
+            .unwrap_or_else(|e| fail(e)),
+        None => FIELDS.iter().map(|&field| Column::from(field)).collect(),
+    };
+    let format = match options.format.as_deref() {
+        None | Some("human") => Format::Human {
+            chart: !options.compact,
+            names: options.gvl.map(|fname| load_vendor_list(&fname)),
+        },
+        Some("json") => Format::Json,
+        Some("csv") => Format::Table(columns, ','),
+        Some("tsv") => Format::Table(columns, '\t'),
+        Some("bits") => Format::Bits,
+        Some(other) => fail(format!("Unrecognized format {}", other)),
+    };
+    if let Format::Human {
+        chart: true,
+        names: Some(_),
+    } = format
+    {
+        fail("--gvl can only be used with --compact");
+    }
+    if let Format::Table(ref columns, separator) = format {
+        println!(
+            "{}",
+            join_cells(columns.iter().map(|column| column.to_string()), separator)
+        );
+    }
+
+    if let Some(consent_str) = string {
+        match decode(&consent_str, &format) {
+            Ok(output) => println!("{}", output),
+            Err(e) => fail(e),
+        }
+        return;
+    }
+    let records = source.open(OnError::Warn);
+    records.for_each(
+        move |_, _, consent_str| decode(consent_str, &format),
+        |output| println!("{}", output),
+    );
+}
+
+fn decode(consent_str: &str, format: &Format) -> Result<String, String> {
+    if let Format::Bits = format {
+        // Dumped without parsing, so that malformed strings can be inspected
+        return bits::dump(consent_str);
+    }
+    let gdpr = ConsentString::try_parse(consent_str)
+        .map_err(|e| format!("Unable to decode GDPR consent string: {}", e))?;
+    Ok(match format {
+        Format::Human { chart, names } => render_human(&gdpr, *chart, names.as_ref()),
+        Format::Json => render_json(&gdpr),
+        Format::Table(columns, separator) => join_cells(
+            columns.iter().map(|column| column.cell(&gdpr)),
+            *separator,
+        ),
+        Format::Bits => unreachable!(),
+    })
+}
+
+fn render_json(gdpr: &ConsentString) -> String {
+    let purposes = gdpr.purposes_allowed
+        .entries()
+        .map(|&(id, _, _)| id)
+        .collect();
+    let consents: Vec<_> = gdpr.vendor_consents
+        .iter()
+        .enumerate()
+        .filter_map(|(id, &value)| {
+            if id > 0 && value {
+                Some(id as u16)
+            } else {
+                None
+            }
+        })
+        .collect();
+    let gdpr = SConsentString {
+        version: gdpr.version,
+        created: deciseconds(&gdpr.created),
+        last_updated: deciseconds(&gdpr.last_updated),
+        cmp_id: gdpr.cmp_id,
+        cmp_version: gdpr.cmp_version,
+        consent_screen: gdpr.consent_screen,
+        consent_language: gdpr.consent_language.iter().collect::<String>(),
+        vendor_list_version: gdpr.vendor_list_version,
+        purposes_allowed: purposes,
+        max_vendor_id: gdpr.max_vendor_id,
+        vendor_consents: consents,
+    };
+
+    serde_json::to_string(&gdpr).expect("Unable to serialize JSON")
+}
+
+fn render_human(gdpr: &ConsentString, chart: bool, names: Option<&VendorList>) -> String {
+    let purposes = gdpr.purposes_allowed
+        .entries()
+        .map(|&(_, _, name)| name)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let consents = if !chart {
+        render_ranges(gdpr, names)
+    } else {
+        let mut rv = vec![];
+        let rows = (gdpr.max_vendor_id / 100 + 1) as usize;
+        for row in 0..rows {
+            if row % 10 == 0 {
+                rv.push("    0000000000 1111111111 2222222222 3333333333 4444444444 5555555555 6666666666 7777777777 8888888888 9999999999".to_string());
+                rv.push("    0123456789 0123456789 0123456789 0123456789 0123456789 0123456789 0123456789 0123456789 0123456789 0123456789".to_string());
+            }
+            let mut row_str = format!("{:3}", row);
+            for vid in (100 * row)..cmp::min(100 * (1 + row), (gdpr.max_vendor_id + 1) as usize) {
+                if vid % 10 == 0 {
+                    row_str.push(' ');
+                }
+                if vid == 0 {
+                    row_str.push(' ')
+                } else {
+                    row_str.push(if gdpr.vendor_consents[vid] { '#' } else { ' ' });
+                }
+            }
+            rv.push(row_str);
+        }
+        rv.join("\n")
+    };
+    format!(
+        "
+GDPR Consent String (v{version})
+Created {created}; last updated {last_updated}
+CMP Id: {cmp_id} (v{cmp_version})
+Consent screen number: {consent_screen}
+Consent language: {consent_language}
+Vendor list version: {vendor_list_version}
+Purposes allowed: {purposes}
+Vendor consents:
+{consents}
+",
+        version = gdpr.version,
+        created = gdpr.created,
+        last_updated = gdpr.last_updated,
+        cmp_id = gdpr.cmp_id,
+        cmp_version = gdpr.cmp_version,
+        consent_screen = gdpr.consent_screen,
+        consent_language = gdpr.consent_language.iter().collect::<String>(),
+        vendor_list_version = gdpr.vendor_list_version,
+        purposes = purposes,
+        consents = consents
+    )
+}
+
+/// Lists consented and refused vendors as merged ranges of ids, with totals
+fn render_ranges(gdpr: &ConsentString, names: Option<&VendorList>) -> String {
+    let (consented, refused): (Vec<usize>, Vec<usize>) =
+        (1..gdpr.vendor_consents.len()).partition(|&id| gdpr.vendor_consents[id]);
+    let total = consented.len() + refused.len();
+    let mut rv = vec![format!(
+        "{} of {} vendors consented ({:.2}%)",
+        consented.len(),
+        total,
+        if total == 0 {
+            0.0
+        } else {
+            100.0 * consented.len() as f64 / total as f64
+        }
+    )];
+    for (label, ids) in &[("Consented", consented), ("Refused", refused)] {
+        rv.push(format!(
+            "  {}: {}",
+            label,
+            if ids.is_empty() {
+                "none".to_string()
+            } else {
+                ranges(ids.iter().cloned())
+            }
+        ));
+        if let Some(gvl) = names {
+            for &id in ids {
+                if let Some(vendor) = gvl.vendor(id as u16) {
+                    rv.push(format!("    {:>5}  {}", id, vendor.name));
+                }
+            }
+        }
+    }
+    rv.join("\n")
+}
+
+/// Prints the differences between two consent strings. Like `diff`, returns 0 if they are the
+/// same, 1 if they differ and 2 if either cannot be decoded.
+pub fn diff(a: &str, b: &str) -> i32 {
+    let (a, b) = match (ConsentString::try_parse(a), ConsentString::try_parse(b)) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) => {
+            eprintln!("Unable to decode A: {}", e);
+            return 2;
+        }
+        (_, Err(e)) => {
+            eprintln!("Unable to decode B: {}", e);
+            return 2;
+        }
+    };
+    let purposes =
+        |purposes: Purposes| ranges(purposes.entries().map(|&(id, _, _)| id as usize));
+    let vendors = |ids: Vec<u16>| ranges(ids.into_iter().map(|id| id as usize));
+    let changes: Vec<_> = a.diff(&b)
+        .into_iter()
+        .map(|change| {
+            let field = match change {
+                Change::Version(..) => Field::Version,
+                Change::Created(..) => Field::Created,
+                Change::LastUpdated(..) => Field::LastUpdated,
+                Change::CmpId(..) => Field::CmpId,
+                Change::CmpVersion(..) => Field::CmpVersion,
+                Change::ConsentScreen(..) => Field::ConsentScreen,
+                Change::ConsentLanguage(..) => Field::ConsentLanguage,
+                Change::VendorListVersion(..) => Field::VendorListVersion,
+                Change::MaxVendorId(..) => Field::MaxVendorId,
+                Change::PurposesAdded(p) => return format!("purposes added: {}", purposes(p)),
+                Change::PurposesRemoved(p) => return format!("purposes removed: {}", purposes(p)),
+                Change::VendorsGained(ids) => {
+                    return format!("vendors gained consent: {}", vendors(ids))
+                }
+                Change::VendorsLost(ids) => {
+                    return format!("vendors lost consent: {}", vendors(ids))
+                }
+            };
+            let column = Column::from(field);
+            format!("{}: {} -> {}", column, column.cell(&a), column.cell(&b))
+        })
+        .collect();
+    if changes.is_empty() {
+        println!("No differences");
+        0
+    } else {
+        println!("{}", changes.join("\n"));
+        1
+    }
+}
@@ -0,0 +1,220 @@
+use chrono::{DateTime, Utc};
+use cli::decode::SConsentString;
+use cli::fail;
+use cli::input::InputFile;
+use cli::output::parse_ranges;
+use serde_json;
+use std::io::BufRead;
+use {deciseconds, time, ConsentString, Purpose, Purposes};
+
+/// Fields that can be set from the command line, overriding any JSON input
+#[derive(StructOpt, Debug)]
+pub struct Fields {
+    /// Creation time, as RFC 3339 or deciseconds since the epoch; defaults to now
+    #[structopt(long = "created")]
+    created: Option<String>,
+
+    /// Last update time, as RFC 3339 or deciseconds since the epoch; defaults to now
+    #[structopt(long = "last-updated")]
+    last_updated: Option<String>,
+
+    #[structopt(long = "cmp-id")]
+    cmp_id: Option<u16>,
+
+    #[structopt(long = "cmp-version")]
+    cmp_version: Option<u16>,
+
+    #[structopt(long = "consent-screen")]
+    consent_screen: Option<u8>,
+
+    /// Two-letter ISO 639-1 language code
+    #[structopt(long = "language")]
+    language: Option<String>,
+
+    #[structopt(long = "vendor-list-version")]
+    vendor_list_version: Option<u16>,
+
+    /// Allowed purpose ids, such as `1,2,3` or `1-5`
+    #[structopt(long = "purposes")]
+    purposes: Option<String>,
+
+    /// Defaults to the highest vendor id with consent
+    #[structopt(long = "max-vendor-id")]
+    max_vendor_id: Option<u16>,
+
+    /// Vendor ids with consent, such as `1-2011`
+    #[structopt(long = "vendors")]
+    vendors: Option<String>,
+
+    /// Vendor ids to remove from --vendors (or the JSON input), such as `9`
+    #[structopt(long = "deny")]
+    deny: Option<String>,
+}
+
+/// Arguments to `gdpr encode`
+#[derive(StructOpt, Debug)]
+pub struct Args {
+    /// A JSON object in the shape printed by `decode -o json`
+    #[structopt(name = "JSON")]
+    pub json: Option<String>,
+
+    #[structopt(flatten)]
+    pub input: InputFile,
+
+    #[structopt(flatten)]
+    pub fields: Fields,
+}
+
+/// Prints the encoded form of each JSON object in the input, or of the string described by the
+/// field options alone. Without JSON, an input file or field options, objects are read from stdin.
+pub fn run(args: Args) {
+    let fields = args.fields;
+    let lines: Box<dyn Iterator<Item = String>> = match (args.json, &args.input.path) {
+        (Some(json), _) => Box::new(Some(json).into_iter()),
+        (None, None) if !fields.is_empty() => {
+            match fields.apply(defaults()).and_then(|gdpr| encode(&gdpr)) {
+                Ok(encoded) => println!("{}", encoded),
+                Err(e) => fail(e),
+            }
+            return;
+        }
+        (None, _) => Box::new(
+            args.input
+                .open()
+                .lines()
+                .map(|line| line.unwrap_or_else(|e| fail(e))),
+        ),
+    };
+    for (idx, line) in lines.enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let encoded = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid JSON: {}", e))
+            .and_then(|gdpr| fields.apply(gdpr))
+            .and_then(|gdpr| encode(&gdpr));
+        match encoded {
+            Ok(encoded) => println!("{}", encoded),
+            Err(e) => fail(format!("Line {}: {}", idx + 1, e)),
+        }
+    }
+}
+
+/// What the field options are applied to when there is no JSON input
+fn defaults() -> SConsentString {
+    let now = deciseconds(&Utc::now());
+    SConsentString {
+        version: 1,
+        created: now,
+        last_updated: now,
+        cmp_id: 0,
+        cmp_version: 0,
+        consent_screen: 0,
+        consent_language: "en".to_string(),
+        vendor_list_version: 0,
+        purposes_allowed: vec![],
+        max_vendor_id: 0,
+        vendor_consents: vec![],
+    }
+}
+
+impl Fields {
+    fn is_empty(&self) -> bool {
+        self.created.is_none() && self.last_updated.is_none() && self.cmp_id.is_none()
+            && self.cmp_version.is_none() && self.consent_screen.is_none()
+            && self.language.is_none() && self.vendor_list_version.is_none()
+            && self.purposes.is_none() && self.max_vendor_id.is_none()
+            && self.vendors.is_none() && self.deny.is_none()
+    }
+
+    fn apply(&self, mut gdpr: SConsentString) -> Result<SConsentString, String> {
+        if let Some(ref created) = self.created {
+            gdpr.created = timestamp(created)?;
+        }
+        if let Some(ref last_updated) = self.last_updated {
+            gdpr.last_updated = timestamp(last_updated)?;
+        }
+        gdpr.cmp_id = self.cmp_id.unwrap_or(gdpr.cmp_id);
+        gdpr.cmp_version = self.cmp_version.unwrap_or(gdpr.cmp_version);
+        gdpr.consent_screen = self.consent_screen.unwrap_or(gdpr.consent_screen);
+        if let Some(ref language) = self.language {
+            gdpr.consent_language = language.clone();
+        }
+        gdpr.vendor_list_version = self.vendor_list_version.unwrap_or(gdpr.vendor_list_version);
+        if let Some(ref purposes) = self.purposes {
+            gdpr.purposes_allowed = parse_ranges(purposes)?
+                .into_iter()
+                .map(|id| match id {
+                    1..=5 => Ok(id as u8),
+                    _ => Err(format!("Unknown purpose {}", id)),
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(ref vendors) = self.vendors {
+            gdpr.vendor_consents = ids(vendors)?;
+            let highest = gdpr.vendor_consents.last().cloned().unwrap_or(0);
+            gdpr.max_vendor_id = gdpr.max_vendor_id.max(highest);
+        }
+        if let Some(ref deny) = self.deny {
+            let deny = ids(deny)?;
+            gdpr.vendor_consents.retain(|id| !deny.contains(id));
+        }
+        gdpr.max_vendor_id = self.max_vendor_id.unwrap_or(gdpr.max_vendor_id);
+        Ok(gdpr)
+    }
+}
+
+fn ids(s: &str) -> Result<Vec<u16>, String> {
+    parse_ranges(s)?
+        .into_iter()
+        .map(|id| match id {
+            1..=65535 => Ok(id as u16),
+            _ => Err(format!("Vendor id {} is out of range", id)),
+        })
+        .collect()
+}
+
+fn timestamp(s: &str) -> Result<u64, String> {
+    s.parse().or_else(|_| {
+        DateTime::parse_from_rfc3339(s)
+            .map(|time| deciseconds(&time.with_timezone(&Utc)))
+            .map_err(|_| format!("Invalid timestamp {}", s))
+    })
+}
+
+fn encode(gdpr: &SConsentString) -> Result<String, String> {
+    let time = |deciseconds| time(deciseconds).ok_or_else(|| format!("Timestamp {} is out of range", deciseconds));
+    let language: Vec<char> = gdpr.consent_language.to_lowercase().chars().collect();
+    let consent_language = match language.as_slice() {
+        &[a, b] if a.is_ascii_lowercase() && b.is_ascii_lowercase() => [a, b],
+        _ => return Err(format!("Invalid language {}", gdpr.consent_language)),
+    };
+    let mut purposes_allowed = Purposes::none();
+    for &id in &gdpr.purposes_allowed {
+        purposes_allowed |=
+            Purpose::from_id(id as u64).ok_or_else(|| format!("Unknown purpose {}", id))?;
+    }
+    let mut vendor_consents = vec![false; gdpr.max_vendor_id as usize + 1];
+    for &id in &gdpr.vendor_consents {
+        match vendor_consents.get_mut(id as usize) {
+            Some(consent) if id > 0 => *consent = true,
+            _ => {
+                return Err(format!(
+                    "Vendor id {} is outside 1-{}",
+                    id, gdpr.max_vendor_id
+                ))
+            }
+        }
+    }
+    ConsentString {
+        version: gdpr.version,
+        created: time(gdpr.created)?,
+        last_updated: time(gdpr.last_updated)?,
+        cmp_id: gdpr.cmp_id,
+        cmp_version: gdpr.cmp_version,
+        consent_screen: gdpr.consent_screen,
+        consent_language,
+        vendor_list_version: gdpr.vendor_list_version,
+        purposes_allowed,
+        max_vendor_id: gdpr.max_vendor_id,
+        vendor_consents,
+    }.encode()
+        .map_err(|e| e.to_string())
+}
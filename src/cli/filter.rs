@@ -0,0 +1,221 @@
+use cli::input::{OnError, Source};
+use cli::output::{join_cells, Column};
+use cli::{fail, load_vendor_list};
+use filter::{Field, Filter, Value};
+use serde_json;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use ConsentString;
+
+#[derive(StructOpt, Debug)]
+pub struct Options {
+    /// The filter expression
+    #[structopt(short = "e", long = "expression")]
+    expression: Option<String>,
+
+    /// Global Vendor List JSON, used to resolve `vendor("name")` references
+    #[structopt(long = "gvl", parse(from_os_str))]
+    gvl: Option<PathBuf>,
+
+    /// Print the number of matching strings instead of the strings themselves
+    #[structopt(short = "c", long = "count")]
+    count: bool,
+
+    /// Select strings that do not match the expression
+    #[structopt(short = "v", long = "invert")]
+    invert: bool,
+
+    /// Print the number of matching strings for each value of a field
+    #[structopt(long = "group-by")]
+    group_by: Option<Field>,
+
+    /// Print how often each id of a list field (consents or purposes) appears in matching strings
+    #[structopt(long = "histogram")]
+    histogram: Option<Field>,
+
+    /// Print the given comma-separated fields of matching strings, e.g. `cmpid,count(consents)`
+    #[structopt(short = "s", long = "select")]
+    select: Option<String>,
+
+    /// Output format for --select: tsv, csv or json
+    #[structopt(short = "o", long = "output")]
+    format: Option<String>,
+
+    /// Print how each part of the expression evaluated for every input line
+    #[structopt(long = "explain")]
+    explain: bool,
+}
+
+/// Arguments to `gdpr filter`
+#[derive(StructOpt, Debug)]
+pub struct Args {
+    /// File to read the filter program from, instead of giving it with -e
+    #[structopt(short = "p", long = "program", parse(from_os_str))]
+    pub program: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    pub options: Options,
+
+    #[structopt(flatten)]
+    pub source: Source,
+}
+
+/// What a matching line produces
+enum Outcome {
+    Skipped,
+    Matched(String, ConsentString),
+    Explained(String),
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Format {
+    /// Cells separated by `,` (quoted as CSV) or tab
+    Table(char),
+    Json,
+}
+
+enum Report {
+    Lines,
+    Select(Vec<Column>, Format),
+    Count(u64),
+    GroupBy(Field, BTreeMap<String, u64>),
+    Histogram(Field, u64, BTreeMap<u64, u64>),
+}
+
+impl Report {
+    fn add(&mut self, line: &str, gdpr: &ConsentString) {
+        match self {
+            Report::Lines => println!("{}", line),
+            Report::Select(columns, format) => print_row(columns, *format, gdpr),
+            Report::Count(count) => *count += 1,
+            Report::GroupBy(field, groups) => {
+                *groups.entry(field.get(gdpr).to_string()).or_insert(0) += 1;
+            }
+            Report::Histogram(field, total, ids) => {
+                *total += 1;
+                if let Value::Vec(v) = field.get(gdpr) {
+                    for id in v {
+                        *ids.entry(id).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(self) {
+        match self {
+            Report::Lines | Report::Select(..) => (),
+            Report::Count(count) => println!("{}", count),
+            Report::GroupBy(_, groups) => for (value, count) in groups {
+                println!("{}\t{}", value, count);
+            },
+            Report::Histogram(_, total, ids) => for (id, count) in ids {
+                println!(
+                    "{}\t{}\t{:.2}%",
+                    id,
+                    count,
+                    100.0 * count as f64 / total as f64
+                );
+            },
+        }
+    }
+}
+
+fn print_row(columns: &[Column], format: Format, gdpr: &ConsentString) {
+    match format {
+        Format::Table(separator) => println!(
+            "{}",
+            join_cells(columns.iter().map(|column| column.cell(gdpr)), separator)
+        ),
+        Format::Json => {
+            let mut row = serde_json::Map::new();
+            for column in columns {
+                row.insert(column.to_string(), column.get(gdpr));
+            }
+            println!("{}", serde_json::Value::Object(row));
+        }
+    }
+}
+
+/// Prints the strings in the input that match the filter program, or a report on them.
+pub fn run(options: Options, program: Option<&Path>, source: &Source) {
+    let gvl = options.gvl.map(|fname| load_vendor_list(&fname));
+    let filter = match (options.expression, program) {
+        (Some(expr), _) => Filter::compile_with(gvl.as_ref(), &expr),
+        (None, Some(fname)) => Filter::compile_file(gvl.as_ref(), fname),
+        (None, None) => fail("You must provide either an expression or filename"),
+    };
+    let filter = filter.unwrap_or_else(|e| fail(e));
+    let format = match options.format.as_deref() {
+        None | Some("tsv") => Format::Table('\t'),
+        Some("csv") => Format::Table(','),
+        Some("json") => Format::Json,
+        Some(other) => fail(format!("Unrecognized format {}", other)),
+    };
+    let columns = options.select.map(|select| {
+        select
+            .split(',')
+            .map(|column| column.parse())
+            .collect::<Result<Vec<Column>, _>>()
+            .unwrap_or_else(|e| fail(e))
+    });
+    let explain = options.explain;
+    if explain
+        && (options.count || options.group_by.is_some() || options.histogram.is_some()
+            || options.invert || columns.is_some())
+    {
+        fail(
+            "--explain cannot be combined with --count, --group-by, --histogram, --invert or \
+             --select",
+        );
+    }
+    let mut report = match (options.count, options.group_by, options.histogram, columns) {
+        (false, None, None, None) => Report::Lines,
+        (false, None, None, Some(columns)) => {
+            if let Format::Table(separator) = format {
+                println!(
+                    "{}",
+                    join_cells(columns.iter().map(|column| column.to_string()), separator)
+                );
+            }
+            Report::Select(columns, format)
+        }
+        (true, None, None, None) => Report::Count(0),
+        (false, Some(field), None, None) => Report::GroupBy(field, BTreeMap::new()),
+        (false, None, Some(field @ Field::Purposes), None)
+        | (false, None, Some(field @ Field::Consents), None) => {
+            Report::Histogram(field, 0, BTreeMap::new())
+        }
+        (false, None, Some(field), None) => {
+            fail(format!("Cannot build a histogram of {}", field))
+        }
+        _ => fail("Only one of --count, --group-by, --histogram and --select may be given"),
+    };
+    let records = source.open(OnError::Skip);
+    if let (Some(header), &Report::Lines) = (records.header(), &report) {
+        println!("{}", header);
+    }
+
+    let invert = options.invert;
+    let work = move |idx: usize, record: &str, consent_str: &str| {
+        let gdpr = ConsentString::try_parse(consent_str).map_err(|e| e.to_string())?;
+        Ok(if explain {
+            Outcome::Explained(format!(
+                "Line {}: {}\n{}",
+                idx + 1,
+                record,
+                filter.expr().explain(&gdpr)
+            ))
+        } else if filter.matches(&gdpr) != invert {
+            Outcome::Matched(record.to_string(), gdpr)
+        } else {
+            Outcome::Skipped
+        })
+    };
+    records.for_each(work, |outcome| match outcome {
+        Outcome::Skipped => (),
+        Outcome::Matched(line, gdpr) => report.add(&line, &gdpr),
+        Outcome::Explained(explanation) => println!("{}", explanation),
+    });
+    report.finish();
+}
@@ -0,0 +1,146 @@
+use chrono::{DateTime, Utc};
+use cli::fail;
+use generate::Config;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use {Generator, VendorEncoding};
+
+/// Arguments to `gdpr generate`. Anything left out takes the library's default distribution.
+#[derive(StructOpt, Debug)]
+pub struct Args {
+    /// How many strings to print
+    #[structopt(short = "n", long = "count", default_value = "1")]
+    pub count: usize,
+
+    /// The same seed and options always print the same strings; defaults to the current time
+    #[structopt(long = "seed")]
+    pub seed: Option<u64>,
+
+    /// Versions to draw from, such as `1` or `1-3`
+    #[structopt(long = "versions", parse(try_from_str = "range"))]
+    pub versions: Option<RangeInclusive<u8>>,
+
+    /// Earliest creation time, as RFC 3339
+    #[structopt(long = "from", parse(try_from_str = "timestamp"))]
+    pub from: Option<DateTime<Utc>>,
+
+    /// Latest creation or update time, as RFC 3339
+    #[structopt(long = "to", parse(try_from_str = "timestamp"))]
+    pub to: Option<DateTime<Utc>>,
+
+    #[structopt(long = "cmp-ids", parse(try_from_str = "range"))]
+    pub cmp_ids: Option<RangeInclusive<u16>>,
+
+    #[structopt(long = "cmp-versions", parse(try_from_str = "range"))]
+    pub cmp_versions: Option<RangeInclusive<u16>>,
+
+    #[structopt(long = "consent-screens", parse(try_from_str = "range"))]
+    pub consent_screens: Option<RangeInclusive<u8>>,
+
+    /// Languages to choose from, such as `en,fr,de`
+    #[structopt(long = "languages", parse(try_from_str = "languages"))]
+    pub languages: Option<Vec<[char; 2]>>,
+
+    #[structopt(long = "vendor-list-versions", parse(try_from_str = "range"))]
+    pub vendor_list_versions: Option<RangeInclusive<u16>>,
+
+    /// The chance that a purpose is allowed: either one probability for all five purposes, or
+    /// five comma-separated ones
+    #[structopt(long = "purposes", parse(try_from_str = "probabilities"))]
+    pub purposes: Option<[f64; 5]>,
+
+    /// Range of max vendor ids, such as `500-2500`
+    #[structopt(long = "max-vendor-ids", parse(try_from_str = "range"))]
+    pub max_vendor_ids: Option<RangeInclusive<u16>>,
+
+    /// The chance that each vendor has consent
+    #[structopt(long = "density", parse(try_from_str = "probability"))]
+    pub density: Option<f64>,
+
+    /// How to encode vendor consents: shortest, bitfield or range
+    #[structopt(long = "encoding", parse(try_from_str = "encoding"))]
+    pub encoding: Option<VendorEncoding>,
+}
+
+/// Prints random consent strings, one per line.
+pub fn run(args: Args) {
+    let default = Config::default();
+    let config = Config {
+        versions: args.versions.unwrap_or(default.versions),
+        created: args.from.unwrap_or(*default.created.start())
+            ..=args.to.unwrap_or(*default.created.end()),
+        cmp_ids: args.cmp_ids.unwrap_or(default.cmp_ids),
+        cmp_versions: args.cmp_versions.unwrap_or(default.cmp_versions),
+        consent_screens: args.consent_screens.unwrap_or(default.consent_screens),
+        languages: args.languages.unwrap_or(default.languages),
+        vendor_list_versions: args.vendor_list_versions.unwrap_or(default.vendor_list_versions),
+        purpose_probabilities: args.purposes.unwrap_or(default.purpose_probabilities),
+        max_vendor_ids: args.max_vendor_ids.unwrap_or(default.max_vendor_ids),
+        consent_density: args.density.unwrap_or(default.consent_density),
+        encoding: args.encoding.unwrap_or(default.encoding),
+    };
+    let seed = args
+        .seed
+        .unwrap_or_else(|| Utc::now().timestamp_nanos_opt().unwrap_or(0) as u64);
+    let generator = Generator::new(config, seed).unwrap_or_else(|e| fail(e));
+    for consent_string in generator.take(args.count) {
+        println!("{}", consent_string);
+    }
+}
+
+fn range<T: FromStr + PartialOrd + Copy>(s: &str) -> Result<RangeInclusive<T>, String> {
+    let mut ends = s.splitn(2, '-').map(|end| {
+        end.trim()
+            .parse::<T>()
+            .map_err(|_| format!("Invalid range {}", s))
+    });
+    let start = ends.next().unwrap()?;
+    let end = ends.next().unwrap_or(Ok(start))?;
+    if end < start {
+        return Err(format!("Invalid range {}", s));
+    }
+    Ok(start..=end)
+}
+
+fn timestamp(s: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| format!("Invalid timestamp {}", s))
+}
+
+fn languages(s: &str) -> Result<Vec<[char; 2]>, String> {
+    s.split(',')
+        .map(|code| {
+            let code: Vec<char> = code.trim().to_lowercase().chars().collect();
+            match code.as_slice() {
+                &[a, b] if a.is_ascii_lowercase() && b.is_ascii_lowercase() => Ok([a, b]),
+                _ => Err(format!("Invalid language {}", s)),
+            }
+        })
+        .collect()
+}
+
+fn probability(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+        _ => Err(format!("Invalid probability {}", s)),
+    }
+}
+
+fn probabilities(s: &str) -> Result<[f64; 5], String> {
+    let values = s.split(',').map(probability).collect::<Result<Vec<_>, _>>()?;
+    match *values.as_slice() {
+        [p] => Ok([p; 5]),
+        [a, b, c, d, e] => Ok([a, b, c, d, e]),
+        _ => Err("Expected one probability, or one for each of the five purposes".to_string()),
+    }
+}
+
+fn encoding(s: &str) -> Result<VendorEncoding, String> {
+    match s {
+        "shortest" => Ok(VendorEncoding::Shortest),
+        "bitfield" => Ok(VendorEncoding::BitField),
+        "range" => Ok(VendorEncoding::Range),
+        _ => Err(format!("Unrecognized encoding {}", s)),
+    }
+}
@@ -0,0 +1,340 @@
+use cli::{fail, parallel};
+use serde_json;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{stdin, BufRead, BufReader, Cursor};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The file a command reads its input from, shared by every command that reads one
+#[derive(StructOpt, Debug)]
+pub struct InputFile {
+    /// File to read from, one record per line; defaults to stdin
+    #[structopt(name = "input", short = "i", long = "input", parse(from_os_str))]
+    pub path: Option<PathBuf>,
+}
+
+impl InputFile {
+    /// Opens the file, or stdin if none was given.
+    pub fn open(&self) -> Box<dyn BufRead + Send> {
+        match self.path {
+            Some(ref fname) => Box::new(BufReader::new(
+                File::open(fname).unwrap_or_else(|e| fail(format!("Unable to open file: {}", e))),
+            )),
+            None => Box::new(BufReader::new(stdin())),
+        }
+    }
+}
+
+/// How to read records from a stream of consent strings, shared by every command that reads one
+#[derive(StructOpt, Debug)]
+pub struct Source {
+    #[structopt(flatten)]
+    pub input: InputFile,
+
+    /// Read JSON lines, taking the consent string from a dotted path such as `user.consent`
+    #[structopt(long = "json-field")]
+    json_field: Option<String>,
+
+    /// Read tab-separated lines, taking the consent string from a (1-based) column
+    #[structopt(long = "tsv-column")]
+    tsv_column: Option<usize>,
+
+    /// Read CSV with a header row, taking the consent string from the named column
+    #[structopt(long = "csv-column")]
+    csv_column: Option<String>,
+
+    /// What to do with lines that cannot be decoded: skip, warn, fail or emit
+    #[structopt(long = "on-error")]
+    on_error: Option<OnError>,
+
+    /// Number of worker threads used to decode input
+    #[structopt(short = "j", long = "jobs", default_value = "1")]
+    jobs: usize,
+
+    /// With --jobs, print results as soon as they are ready rather than in input order
+    #[structopt(long = "unordered")]
+    unordered: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OnError {
+    /// Drop the line silently
+    Skip,
+    /// Drop the line, reporting it on stderr
+    Warn,
+    /// Report the line on stderr and stop
+    Fail,
+    /// Report the line on stderr and pass it through to stdout
+    Emit,
+}
+
+impl FromStr for OnError {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OnError, String> {
+        match s {
+            "skip" => Ok(OnError::Skip),
+            "warn" => Ok(OnError::Warn),
+            "fail" => Ok(OnError::Fail),
+            "emit" => Ok(OnError::Emit),
+            _ => Err(format!("Unrecognized error mode {}", s)),
+        }
+    }
+}
+
+/// An open stream of records, ready to be processed
+pub struct Records {
+    reader: Box<dyn BufRead + Send>,
+    input: Input,
+    header: Option<String>,
+    on_error: OnError,
+    jobs: usize,
+    ordered: bool,
+}
+
+/// What became of a single record
+enum Outcome<T> {
+    Skipped,
+    Done(T),
+    Rejected(usize, String, String),
+}
+
+impl Source {
+    /// Opens the input file, or stdin if there is none, reading past the header row of CSV
+    /// input. `on_error` is used unless `--on-error` was given.
+    pub fn open(&self, on_error: OnError) -> Records {
+        self.records(self.input.open(), on_error)
+    }
+
+    /// Treats a single string given on the command line as the whole input.
+    pub fn single(&self, string: &str, on_error: OnError) -> Records {
+        self.records(
+            Box::new(Cursor::new(string.as_bytes().to_vec())),
+            on_error,
+        )
+    }
+
+    fn records(&self, mut reader: Box<dyn BufRead + Send>, on_error: OnError) -> Records {
+        let input = match (&self.json_field, self.tsv_column, &self.csv_column) {
+            (None, None, None) => Input::Line,
+            (Some(path), None, None) => Input::json_field(path),
+            (None, Some(column), None) if column > 0 => Input::Column('\t', column - 1),
+            (None, Some(_), None) => fail("Columns are numbered from 1"),
+            // Resolved against the header row below
+            (None, None, Some(_)) => Input::Column(',', 0),
+            _ => fail("Only one of --json-field, --tsv-column and --csv-column may be given"),
+        };
+        let (input, header) = match self.csv_column {
+            Some(ref name) => {
+                let mut header = String::new();
+                reader
+                    .read_line(&mut header)
+                    .unwrap_or_else(|e| fail(format!("Unable to read CSV header: {}", e)));
+                let header = header
+                    .trim_end_matches(['\r', '\n'])
+                    .to_string();
+                let column = split_quoted(&header, ',')
+                    .iter()
+                    .position(|column| column.trim() == name)
+                    .unwrap_or_else(|| fail(format!("CSV header has no column named {}", name)));
+                (Input::Column(',', column), Some(header))
+            }
+            None => (input, None),
+        };
+        Records {
+            reader,
+            input,
+            header,
+            on_error: self.on_error.unwrap_or(on_error),
+            jobs: self.jobs,
+            ordered: !self.unordered,
+        }
+    }
+}
+
+impl Records {
+    /// The header row of CSV input
+    #[cfg(feature = "filter")]
+    pub fn header(&self) -> Option<&str> {
+        self.header.as_deref()
+    }
+
+    /// Runs `work` over every non-blank record, passing it the line number, the whole record and
+    /// the consent string taken from it, and feeds the results to `sink`.
+    ///
+    /// Records that cannot be read, or for which `work` fails, are handled according to
+    /// `--on-error`, and a count of them is reported at the end. Returns that count.
+    pub fn for_each<T, W, S>(self, work: W, mut sink: S) -> usize
+    where
+        T: Send + 'static,
+        W: Fn(usize, &str, &str) -> Result<T, String> + Send + Sync + 'static,
+        S: FnMut(T),
+    {
+        let input = self.input;
+        let header_lines = if self.header.is_some() { 1 } else { 0 };
+        let work = move |idx: usize, record: &str| {
+            let idx = idx + header_lines;
+            let record = match input {
+                Input::Line => record.trim(),
+                _ => record.trim_end_matches('\r'),
+            };
+            if record.trim().is_empty() {
+                return Outcome::Skipped;
+            }
+            match input.extract(record).and_then(|s| work(idx, record, &s)) {
+                Ok(result) => Outcome::Done(result),
+                Err(e) => Outcome::Rejected(idx, record.to_string(), e),
+            }
+        };
+        let on_error = self.on_error;
+        let mut rejected = 0;
+        let sink = |outcome| match outcome {
+            Outcome::Skipped => (),
+            Outcome::Done(result) => sink(result),
+            Outcome::Rejected(idx, line, e) => {
+                rejected += 1;
+                if on_error != OnError::Skip {
+                    eprintln!("Line {}: {}", idx + 1, e);
+                }
+                match on_error {
+                    OnError::Fail => ::std::process::exit(1),
+                    OnError::Emit => println!("{}", line),
+                    _ => (),
+                }
+            }
+        };
+        let result = parallel::for_each_line(self.reader, self.jobs, self.ordered, work, sink);
+        if let Err(e) = result {
+            fail(format!("Error reading input: {}", e));
+        }
+        if rejected > 0 {
+            eprintln!("{} line(s) could not be decoded", rejected);
+        }
+        rejected
+    }
+}
+
+/// Where the consent string is found in each input record
+#[derive(Debug, Clone)]
+enum Input {
+    /// The whole line is the consent string
+    Line,
+    /// Each line is a JSON document; the string is at the given JSON pointer
+    JsonField(String),
+    /// Each line is a row of separated values; the string is in the given zero-based column
+    Column(char, usize),
+}
+
+impl Input {
+    /// Builds a JSON input from a dotted path such as `user.consent`.
+    pub fn json_field(path: &str) -> Input {
+        Input::JsonField(
+            path.split('.')
+                .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+                .collect(),
+        )
+    }
+
+    /// Pulls the consent string out of a record.
+    pub fn extract<'a>(&self, record: &'a str) -> Result<Cow<'a, str>, String> {
+        match self {
+            Input::Line => Ok(Cow::Borrowed(record.trim())),
+            Input::JsonField(pointer) => {
+                let doc: serde_json::Value =
+                    serde_json::from_str(record).map_err(|e| format!("Invalid JSON: {}", e))?;
+                match doc.pointer(pointer) {
+                    Some(serde_json::Value::String(s)) => Ok(Cow::Owned(s.trim().to_string())),
+                    Some(_) => Err(format!("Field {} is not a string", pointer)),
+                    None => Err(format!("Field {} is missing", pointer)),
+                }
+            }
+            Input::Column('\t', column) => record
+                .split('\t')
+                .nth(*column)
+                .map(|field| Cow::Borrowed(field.trim()))
+                .ok_or_else(|| format!("Record has no column {}", column + 1)),
+            Input::Column(separator, column) => split_quoted(record, *separator)
+                .into_iter()
+                .nth(*column)
+                .map(|field| Cow::Owned(field.trim().to_string()))
+                .ok_or_else(|| format!("Record has no column {}", column + 1)),
+        }
+    }
+}
+
+/// Splits a CSV-style record, honouring double-quoted fields with `""` escapes.
+///
+/// Quoted fields may not span lines.
+pub fn split_quoted(record: &str, separator: char) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = record.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => if chars.peek() == Some(&'"') {
+                chars.next();
+                field.push('"');
+            } else {
+                quoted = false;
+            },
+            '"' if field.is_empty() => quoted = true,
+            c if c == separator && !quoted => {
+                fields.push(field);
+                field = String::new();
+            }
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_fields() {
+        assert_eq!(
+            split_quoted(r#"a,"b,c",d"#, ','),
+            vec!["a".to_string(), "b,c".to_string(), "d".to_string()]
+        );
+        assert_eq!(
+            split_quoted(r#""say ""hi""",,"""""#, ','),
+            vec![r#"say "hi""#.to_string(), "".to_string(), r#"""#.to_string()]
+        );
+        assert_eq!(split_quoted("a\t\"b\tc\"", '\t'), vec!["a", "b\tc"]);
+    }
+
+    #[test]
+    fn columns() {
+        let csv = Input::Column(',', 1);
+        assert_eq!(csv.extract(r#"1,"BOE, ",x"#).unwrap(), "BOE,");
+        assert_eq!(csv.extract("1").unwrap_err(), "Record has no column 2");
+        let tsv = Input::Column('\t', 2);
+        assert_eq!(tsv.extract("a\tb\tc\td").unwrap(), "c");
+        assert_eq!(tsv.extract("a\tb").unwrap_err(), "Record has no column 3");
+    }
+
+    #[test]
+    fn json_fields() {
+        let input = Input::json_field("user.consent");
+        assert_eq!(input.extract(r#"{"user": {"consent": " BOE "}}"#).unwrap(), "BOE");
+        assert_eq!(
+            input.extract(r#"{"user": {"consent": 7}}"#).unwrap_err(),
+            "Field /user/consent is not a string"
+        );
+        assert_eq!(
+            input.extract(r#"{"user": {}}"#).unwrap_err(),
+            "Field /user/consent is missing"
+        );
+        assert!(input.extract("{").unwrap_err().starts_with("Invalid JSON"));
+
+        // `~` and `/` are escaped, so they are matched literally within a key
+        let input = Input::json_field("a/b.c~d");
+        assert!(matches!(input, Input::JsonField(ref pointer) if pointer == "/a~1b/c~0d"));
+        assert_eq!(input.extract(r#"{"a/b": {"c~d": "BOE"}}"#).unwrap(), "BOE");
+    }
+}
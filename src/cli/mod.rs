@@ -0,0 +1,34 @@
+//! Command-line front ends, shared by the `gdpr` binary and the older `decode` and `filter`
+//! binaries. Only built with the `cli` feature, and not part of the library API: errors are
+//! reported by exiting the process.
+
+mod bits;
+pub mod decode;
+pub mod encode;
+#[cfg(feature = "filter")]
+pub mod filter;
+#[cfg(feature = "generate")]
+pub mod generate;
+pub mod input;
+pub mod output;
+mod parallel;
+pub mod stats;
+pub mod validate;
+
+use std::fmt::Display;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use VendorList;
+
+/// Reports a fatal error and exits.
+pub fn fail<T: Display>(message: T) -> ! {
+    eprintln!("{}", message);
+    ::std::process::exit(1);
+}
+
+pub fn load_vendor_list(path: &Path) -> VendorList {
+    let f = File::open(path).unwrap_or_else(|e| fail(format!("Could not open vendor list: {}", e)));
+    VendorList::from_reader(BufReader::new(f))
+        .unwrap_or_else(|e| fail(format!("Unable to parse vendor list: {}", e)))
+}
@@ -0,0 +1,129 @@
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+use {ranges, ConsentString, Field};
+
+/// A column of `decode -o csv` or `filter --select` output: a field, or `count(field)` for a list
+/// field. Columns are built by parsing, which checks what is counted.
+#[derive(Debug, Copy, Clone)]
+pub struct Column {
+    field: Field,
+    count: bool,
+}
+
+impl Column {
+    /// The column's value: timestamps in RFC 3339, lists as ranges of ids and the rest as numbers
+    /// or strings.
+    pub fn get(&self, gdpr: &ConsentString) -> Value {
+        if self.count {
+            let count = self.field.count(gdpr).expect("only list fields are counted");
+            return count.into();
+        }
+        match self.field {
+            Field::Version => gdpr.version.into(),
+            Field::Created => gdpr.created.to_rfc3339().into(),
+            Field::LastUpdated => gdpr.last_updated.to_rfc3339().into(),
+            Field::CmpId => gdpr.cmp_id.into(),
+            Field::CmpVersion => gdpr.cmp_version.into(),
+            Field::ConsentScreen => gdpr.consent_screen.into(),
+            Field::ConsentLanguage => gdpr.consent_language.iter().collect::<String>().into(),
+            Field::VendorListVersion => gdpr.vendor_list_version.into(),
+            Field::Purposes => ranges(
+                gdpr.purposes_allowed
+                    .entries()
+                    .map(|&(id, _, _)| id as usize),
+            ).into(),
+            Field::MaxVendorId => gdpr.max_vendor_id.into(),
+            Field::Consents => ranges(
+                gdpr.vendor_consents
+                    .iter()
+                    .enumerate()
+                    .skip(1)
+                    .filter(|&(_, &consent)| consent)
+                    .map(|(id, _)| id),
+            ).into(),
+        }
+    }
+
+    /// The column's value as a csv or tsv cell
+    pub fn cell(&self, gdpr: &ConsentString) -> String {
+        match self.get(gdpr) {
+            Value::String(s) => s,
+            value => value.to_string(),
+        }
+    }
+}
+
+impl From<Field> for Column {
+    fn from(field: Field) -> Column {
+        Column {
+            field,
+            count: false,
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Column, String> {
+        let s = s.trim();
+        if s.starts_with("count(") && s.ends_with(')') {
+            Ok(Column {
+                field: s[6..s.len() - 1].parse::<Field>()?.countable()?,
+                count: true,
+            })
+        } else {
+            Ok(Column::from(s.parse::<Field>()?))
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.count {
+            write!(f, "count({})", self.field)
+        } else {
+            write!(f, "{}", self.field)
+        }
+    }
+}
+
+/// Joins cells into a csv row, quoting them as needed, or into a tsv row.
+pub fn join_cells<I: Iterator<Item = String>>(cells: I, separator: char) -> String {
+    let cells: Vec<_> = match separator {
+        ',' => cells.map(|cell| csv_escape(&cell)).collect(),
+        _ => cells.collect(),
+    };
+    cells.join(&separator.to_string())
+}
+
+/// Quotes a CSV cell if it contains a separator, quote or newline.
+pub fn csv_escape(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Parses a list of ids and ranges, such as `1-8,10-2011`, into ascending ids.
+pub fn parse_ranges(s: &str) -> Result<Vec<usize>, String> {
+    let mut ids = vec![];
+    for part in s.split(',').map(|part| part.trim()).filter(|part| !part.is_empty()) {
+        let mut ends = part.splitn(2, '-').map(|end| {
+            end.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid id or range {}", part))
+        });
+        let start = ends.next().unwrap()?;
+        let end = ends.next().unwrap_or(Ok(start))?;
+        if end < start {
+            return Err(format!("Invalid range {}", part));
+        }
+        ids.extend(start..=end);
+    }
+    ids.sort();
+    ids.dedup();
+    Ok(ids)
+}
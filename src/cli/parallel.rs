@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, sync_channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Number of lines handed to a worker at a time
+const CHUNK_SIZE: usize = 1024;
+
+/// Number of chunks per worker that may be read but not yet passed to `sink`
+const CHUNKS_IN_FLIGHT: usize = 4;
+
+/// Runs `work` over every line of `reader`, feeding the results to `sink`.
+///
+/// With more than one job, lines are split into chunks which are processed on `jobs` worker threads.
+/// If `ordered` is set, results reach `sink` in input order; otherwise they arrive as soon as their
+/// chunk is done. `work` is given the zero-based line number along with the line. If reading fails,
+/// every line read before the failure still reaches `sink` before the error is returned. If `work`
+/// panics on a worker thread, the panic is resumed on the calling thread.
+pub fn for_each_line<R, T, W, S>(
+    reader: R,
+    jobs: usize,
+    ordered: bool,
+    work: W,
+    mut sink: S,
+) -> io::Result<()>
+where
+    R: BufRead + Send + 'static,
+    T: Send + 'static,
+    W: Fn(usize, &str) -> T + Send + Sync + 'static,
+    S: FnMut(T),
+{
+    if jobs <= 1 {
+        for (idx, line) in reader.lines().enumerate() {
+            sink(work(idx, &line?));
+        }
+        return Ok(());
+    }
+
+    let (chunk_tx, chunk_rx) = sync_channel::<(usize, Vec<String>)>(jobs * 2);
+    // One credit per chunk allowed in flight, returned once the chunk reaches `sink`, so that a
+    // slow chunk can't leave an unbounded number of later ones waiting in `pending`
+    let (credit_tx, credit_rx) = channel::<()>();
+    for _ in 0..jobs * CHUNKS_IN_FLIGHT {
+        credit_tx.send(()).unwrap();
+    }
+    let chunk_rx = Arc::new(Mutex::new(chunk_rx));
+    let (result_tx, result_rx) = channel::<(usize, thread::Result<Vec<T>>)>();
+    let work = Arc::new(work);
+    let workers: Vec<_> = (0..jobs)
+        .map(|_| {
+            let chunk_rx = chunk_rx.clone();
+            let result_tx = result_tx.clone();
+            let work = work.clone();
+            thread::spawn(move || loop {
+                let next = chunk_rx.lock().unwrap().recv();
+                let (chunk_idx, lines) = match next {
+                    Ok(chunk) => chunk,
+                    Err(_) => break,
+                };
+                let first = chunk_idx * CHUNK_SIZE;
+                // A panic is passed on rather than losing the chunk, which would leave an ordered
+                // run waiting for it forever
+                let results = panic::catch_unwind(AssertUnwindSafe(|| {
+                    lines
+                        .iter()
+                        .enumerate()
+                        .map(|(i, line)| work(first + i, line))
+                        .collect()
+                }));
+                if result_tx.send((chunk_idx, results)).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect();
+    drop(result_tx);
+
+    let reader = thread::spawn(move || -> io::Result<()> {
+        let mut lines = reader.lines();
+        for chunk_idx in 0.. {
+            if credit_rx.recv().is_err() {
+                break;
+            }
+            let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+            let mut error = None;
+            for line in lines.by_ref().take(CHUNK_SIZE) {
+                match line {
+                    Ok(line) => chunk.push(line),
+                    Err(err) => {
+                        error = Some(err);
+                        break;
+                    }
+                }
+            }
+            let done = chunk.is_empty() || chunk_tx.send((chunk_idx, chunk)).is_err();
+            if let Some(err) = error {
+                return Err(err);
+            }
+            if done {
+                break;
+            }
+        }
+        Ok(())
+    });
+
+    // Chunks that finished ahead of their turn, keyed by chunk index
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    for (chunk_idx, results) in result_rx {
+        let results = results.unwrap_or_else(|payload| panic::resume_unwind(payload));
+        if !ordered {
+            results.into_iter().for_each(&mut sink);
+            let _ = credit_tx.send(());
+            continue;
+        }
+        pending.insert(chunk_idx, results);
+        while let Some(results) = pending.remove(&next) {
+            results.into_iter().for_each(&mut sink);
+            let _ = credit_tx.send(());
+            next += 1;
+        }
+    }
+    for worker in workers {
+        worker.join().expect("Worker thread panicked");
+    }
+    reader.join().expect("Reader thread panicked")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Cursor, Read};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Serves numbered lines of eight bytes each, counting the bytes read, and then fails if
+    /// `fail` is set
+    struct Lines {
+        data: Cursor<Vec<u8>>,
+        read: Arc<AtomicUsize>,
+        fail: bool,
+    }
+
+    impl Lines {
+        fn new(count: usize, fail: bool) -> Lines {
+            let text: String = (0..count).map(|i| format!("{:07}\n", i)).collect();
+            Lines {
+                data: Cursor::new(text.into_bytes()),
+                read: Arc::new(AtomicUsize::new(0)),
+                fail,
+            }
+        }
+    }
+
+    impl Read for Lines {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.data.read(buf)?;
+            if n == 0 && self.fail {
+                return Err(io::Error::other("disk on fire"));
+            }
+            self.read.fetch_add(n, Ordering::SeqCst);
+            Ok(n)
+        }
+    }
+
+    /// Parses each line, holding up the first chunk so that later ones finish before it
+    fn work(idx: usize, line: &str) -> (usize, usize) {
+        if idx == 0 {
+            thread::sleep(Duration::from_millis(20));
+        }
+        (idx, line.parse().unwrap())
+    }
+
+    #[test]
+    fn ordered() {
+        let count = CHUNK_SIZE * 5 + 7;
+        for &jobs in &[1, 4] {
+            let mut seen = vec![];
+            let reader = BufReader::new(Lines::new(count, false));
+            for_each_line(reader, jobs, true, work, |result| seen.push(result)).unwrap();
+            assert_eq!(seen, (0..count).map(|i| (i, i)).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn unordered() {
+        let count = CHUNK_SIZE * 5 + 7;
+        let mut seen = vec![];
+        let reader = BufReader::new(Lines::new(count, false));
+        for_each_line(reader, 4, false, work, |result| seen.push(result)).unwrap();
+        seen.sort();
+        assert_eq!(seen, (0..count).map(|i| (i, i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn read_error() {
+        let count = CHUNK_SIZE * 2 + 10;
+        for &jobs in &[1, 4] {
+            let mut seen = vec![];
+            let reader = BufReader::new(Lines::new(count, true));
+            let result = for_each_line(reader, jobs, true, work, |result| seen.push(result));
+            assert_eq!(result.unwrap_err().to_string(), "disk on fire");
+            assert_eq!(seen, (0..count).map(|i| (i, i)).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn back_pressure() {
+        let jobs = 2;
+        let lines = Lines::new(CHUNK_SIZE * 100, false);
+        let read = lines.read.clone();
+        let mut read_at_first = None;
+        let mut seen = 0;
+        for_each_line(BufReader::new(lines), jobs, true, work, |_| {
+            if read_at_first.is_none() {
+                // Give the reader time to run as far ahead as it is allowed to
+                thread::sleep(Duration::from_millis(100));
+                read_at_first = Some(read.load(Ordering::SeqCst));
+            }
+            seen += 1;
+        }).unwrap();
+        assert_eq!(seen, CHUNK_SIZE * 100);
+        // Every chunk in flight, plus whatever the BufReader has buffered
+        let limit = jobs * CHUNKS_IN_FLIGHT * CHUNK_SIZE * 8 + 8192;
+        assert!(read_at_first.unwrap() <= limit);
+    }
+
+    #[test]
+    #[should_panic(expected = "bad line")]
+    fn worker_panic() {
+        let reader = BufReader::new(Lines::new(CHUNK_SIZE * 5, false));
+        let work = |idx: usize, _: &str| {
+            if idx == 5 {
+                panic!("bad line");
+            }
+        };
+        for_each_line(reader, 4, true, work, |_| ()).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+use chrono::{DateTime, Duration, Utc};
+use cli::input::{OnError, Source};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use {ConsentString, PURPOSES};
+
+/// Arguments to `gdpr stats`
+#[derive(StructOpt, Debug)]
+pub struct Args {
+    #[structopt(flatten)]
+    pub source: Source,
+}
+
+/// Prints aggregate statistics over every string in the input.
+pub fn run(source: &Source) {
+    let now = Utc::now();
+    let mut stats = Stats::default();
+    stats.failed = source.open(OnError::Skip).for_each(
+        |_, _, consent_str| ConsentString::try_parse(consent_str).map_err(|e| e.to_string()),
+        |gdpr| stats.add(&gdpr, now),
+    ) as u64;
+    println!("{}", stats.report());
+}
+
+/// Upper bounds (in days) of the buckets used for the age of `last_updated`
+const AGES: [(i64, &str); 5] = [
+    (1, "under a day"),
+    (7, "1-7 days"),
+    (30, "7-30 days"),
+    (90, "30-90 days"),
+    (365, "90-365 days"),
+];
+
+/// Aggregate statistics over a stream of consent strings
+#[derive(Debug, Default)]
+pub struct Stats {
+    decoded: u64,
+    failed: u64,
+    versions: BTreeMap<u8, u64>,
+    cmp_ids: BTreeMap<u16, u64>,
+    languages: BTreeMap<String, u64>,
+    vendor_list_versions: BTreeMap<u16, u64>,
+    purposes: [u64; 5],
+    /// Number of strings consenting to each vendor, indexed by vendor id
+    vendors: Vec<u64>,
+    /// Number of strings in each of the `AGES` buckets, then older, then in the future
+    ages: [u64; 7],
+}
+
+impl Stats {
+    /// Counts a string, taking its age relative to `now`.
+    pub fn add(&mut self, gdpr: &ConsentString, now: DateTime<Utc>) {
+        self.decoded += 1;
+        *self.versions.entry(gdpr.version).or_insert(0) += 1;
+        *self.cmp_ids.entry(gdpr.cmp_id).or_insert(0) += 1;
+        *self.languages
+            .entry(gdpr.consent_language.iter().collect())
+            .or_insert(0) += 1;
+        *self.vendor_list_versions
+            .entry(gdpr.vendor_list_version)
+            .or_insert(0) += 1;
+        for (count, &(_, purpose, _)) in self.purposes.iter_mut().zip(PURPOSES.iter()) {
+            if gdpr.purposes_allowed.contains(purpose) {
+                *count += 1;
+            }
+        }
+        if self.vendors.len() < gdpr.vendor_consents.len() {
+            self.vendors.resize(gdpr.vendor_consents.len(), 0);
+        }
+        for (count, &consent) in self.vendors.iter_mut().zip(&gdpr.vendor_consents).skip(1) {
+            if consent {
+                *count += 1;
+            }
+        }
+        let age = now.signed_duration_since(gdpr.last_updated);
+        let bucket = if age < Duration::zero() {
+            AGES.len() + 1
+        } else {
+            AGES.iter()
+                .position(|&(days, _)| age < Duration::days(days))
+                .unwrap_or(AGES.len())
+        };
+        self.ages[bucket] += 1;
+    }
+
+    pub fn report(&self) -> String {
+        let mut out = vec![format!(
+            "Strings: {} decoded, {} could not be decoded",
+            self.decoded, self.failed
+        )];
+        self.section(&mut out, "Versions", &self.versions);
+        self.section(&mut out, "CMP ids", &self.cmp_ids);
+        self.section(&mut out, "Consent languages", &self.languages);
+        self.section(&mut out, "Vendor list versions", &self.vendor_list_versions);
+        out.push("\nPurposes:".to_string());
+        for (&count, &(_, _, name)) in self.purposes.iter().zip(PURPOSES.iter()) {
+            out.push(self.row(name, count));
+        }
+        out.push("\nVendors:".to_string());
+        for (id, &count) in self.vendors.iter().enumerate().skip(1) {
+            if count > 0 {
+                out.push(self.row(id, count));
+            }
+        }
+        out.push("\nLast updated:".to_string());
+        let labels = AGES.iter()
+            .map(|&(_, label)| label)
+            .chain(vec!["over a year", "in the future"]);
+        for (label, &count) in labels.zip(self.ages.iter()) {
+            out.push(self.row(label, count));
+        }
+        out.join("\n")
+    }
+
+    fn section<K: Display>(&self, out: &mut Vec<String>, title: &str, counts: &BTreeMap<K, u64>) {
+        out.push(format!("\n{}:", title));
+        for (key, &count) in counts {
+            out.push(self.row(key, count));
+        }
+    }
+
+    /// Formats a count along with its share of the decoded strings
+    fn row<K: Display>(&self, key: K, count: u64) -> String {
+        format!(
+            "  {:<20} {:>10} {:>7.2}%",
+            key.to_string(),
+            count,
+            if self.decoded == 0 {
+                0.0
+            } else {
+                100.0 * count as f64 / self.decoded as f64
+            }
+        )
+    }
+}
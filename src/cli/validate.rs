@@ -0,0 +1,47 @@
+use cli::input::{OnError, Source};
+use {ConsentString, ParseOptions};
+
+/// Arguments to `gdpr validate`
+#[derive(StructOpt, Debug)]
+pub struct Args {
+    #[structopt(name = "STRING")]
+    pub string: Option<String>,
+
+    /// Reject strings that decode but break the spec, such as those with trailing data or
+    /// non-zero padding, instead of warning about them
+    #[structopt(long = "strict")]
+    pub strict: bool,
+
+    #[structopt(flatten)]
+    pub source: Source,
+}
+
+/// Checks that every string decodes, reporting those that don't and warning about any that break
+/// the spec. Returns the exit status: 0 if all strings are valid and 1 otherwise.
+pub fn run(string: Option<String>, strict: bool, source: &Source) -> i32 {
+    let records = match string {
+        Some(string) => source.single(&string, OnError::Warn),
+        None => source.open(OnError::Warn),
+    };
+    let options = ParseOptions { strict };
+    let mut valid = 0;
+    let invalid = records.for_each(
+        move |idx, _, consent_str| {
+            ConsentString::parse_with(consent_str, &options)
+                .map(|(_, warnings)| (idx, warnings))
+                .map_err(|e| e.to_string())
+        },
+        |(idx, warnings)| {
+            for warning in warnings {
+                eprintln!("Line {}: Warning: {}", idx + 1, warning);
+            }
+            valid += 1;
+        },
+    );
+    println!("{} valid, {} invalid", valid, invalid);
+    if invalid > 0 {
+        1
+    } else {
+        0
+    }
+}
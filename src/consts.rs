@@ -3,7 +3,7 @@ pub(crate) static LETTERS: [char; 26] = [
     't', 'u', 'v', 'w', 'x', 'y', 'z',
 ];
 
-#[cfg_attr(rustfmt, rustfmt_skip)]
+#[rustfmt::skip]
 pub(crate) static REVERSE_BITS: [u8; 64] = [
     0b0000_0000, 0b0010_0000, 0b0001_0000, 0b0011_0000, 0b0000_1000, 0b0010_1000, 0b0001_1000, 0b0011_1000,
     0b0000_0100, 0b0010_0100, 0b0001_0100, 0b0011_0100, 0b0000_1100, 0b0010_1100, 0b0001_1100, 0b0011_1100,
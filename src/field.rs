@@ -0,0 +1,88 @@
+use std::fmt;
+use std::str::FromStr;
+use ConsentString;
+
+/// A field of a consent string, as named in the filter language and in the columns of
+/// `gdpr decode` and `gdpr filter --select`
+#[derive(Debug, Copy, Clone)]
+pub enum Field {
+    Version,
+    Created,
+    LastUpdated,
+    CmpId,
+    CmpVersion,
+    ConsentScreen,
+    ConsentLanguage,
+    VendorListVersion,
+    Purposes,
+    MaxVendorId,
+    Consents,
+}
+
+/// Every field, in encoding order
+pub(crate) const FIELDS: [Field; 11] = [
+    Field::Version,
+    Field::Created,
+    Field::LastUpdated,
+    Field::CmpId,
+    Field::CmpVersion,
+    Field::ConsentScreen,
+    Field::ConsentLanguage,
+    Field::VendorListVersion,
+    Field::Purposes,
+    Field::MaxVendorId,
+    Field::Consents,
+];
+
+/// In the same order as `FIELDS`
+const FIELD_NAMES: [&str; 11] = [
+    "version",
+    "created",
+    "lastupdated",
+    "cmpid",
+    "cmpversion",
+    "consentscreen",
+    "consentlanguage",
+    "vendorlistversion",
+    "purposes",
+    "maxvendorid",
+    "consents",
+];
+
+impl Field {
+    /// The number of ids in a list field, counted without collecting them, or `None` for the
+    /// other fields
+    pub fn count(&self, gdpr: &ConsentString) -> Option<usize> {
+        match self {
+            Field::Purposes => Some(gdpr.purposes_allowed.entries().count()),
+            Field::Consents => Some(gdpr.vendor_consents.iter().skip(1).filter(|&&c| c).count()),
+            _ => None,
+        }
+    }
+
+    /// Checks that the field is a list, so that it can be counted.
+    pub(crate) fn countable(self) -> Result<Field, String> {
+        match self {
+            Field::Purposes | Field::Consents => Ok(self),
+            field => Err(format!("Cannot count {}", field)),
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(FIELD_NAMES[*self as usize])
+    }
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Field, String> {
+        FIELD_NAMES
+            .iter()
+            .position(|&name| name == s)
+            .map(|idx| FIELDS[idx])
+            .ok_or_else(|| format!("Unknown field {}", s))
+    }
+}
@@ -0,0 +1,725 @@
+use {deciseconds, ranges, ConsentString, Field, VendorList};
+use std::cmp;
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Op(Field, Opcode, Value),
+    /// A comparison between two arithmetic expressions, such as `lastupdated - created > 30 days`
+    Compare(Operand, Opcode, Operand),
+    Not(Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    In,
+    NotIn,
+    OneOf,
+    NotOneOf,
+    Like,
+    NotLike,
+}
+
+/// An integer-valued expression on either side of a comparison
+#[derive(Debug, Clone)]
+pub enum Operand {
+    Field(Field),
+    Count(Field),
+    Int(u64),
+    /// A length of time in deciseconds, the unit of `created` and `lastupdated`
+    Duration(u64),
+    Add(Box<Operand>, Box<Operand>),
+    Sub(Box<Operand>, Box<Operand>),
+    Mul(Box<Operand>, Box<Operand>),
+    Div(Box<Operand>, Box<Operand>),
+}
+
+/// How deeply expressions may nest. Evaluating, compiling and dropping an expression all recurse
+/// through it, so deeper ones could overflow the stack.
+pub const MAX_DEPTH: usize = 256;
+
+/// Pairs a node being parsed with its depth, rejecting it if it is nested too deeply.
+pub(crate) fn nested<T>(node: T, depth: usize) -> Result<(T, usize), String> {
+    if depth > MAX_DEPTH {
+        return Err(format!("Expressions may not be nested more than {} deep", MAX_DEPTH));
+    }
+    Ok((node, depth))
+}
+
+/// What an `Operand` measures; only operands of the same type can be compared
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Type {
+    Number,
+    Timestamp,
+    Duration,
+}
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(u64),
+    Str(String),
+    Vec(Vec<u64>),
+    StrVec(Vec<String>),
+}
+
+impl Expr {
+    /// Builds a comparison, rejecting operators that make no sense for the field and normalising
+    /// language literals to lowercase.
+    pub fn op(field: Field, opcode: Opcode, value: Value) -> Result<Expr, String> {
+        let value = match (field, opcode, value) {
+            (Field::ConsentLanguage, Opcode::Eq, Value::Str(s))
+            | (Field::ConsentLanguage, Opcode::Ne, Value::Str(s)) => Value::Str(language_code(&s)?),
+            (Field::ConsentLanguage, Opcode::OneOf, Value::StrVec(v))
+            | (Field::ConsentLanguage, Opcode::NotOneOf, Value::StrVec(v)) => Value::StrVec(
+                v.iter()
+                    .map(|s| language_code(s))
+                    .collect::<Result<_, _>>()?,
+            ),
+            (Field::ConsentLanguage, Opcode::Like, Value::Str(s))
+            | (Field::ConsentLanguage, Opcode::NotLike, Value::Str(s)) => {
+                let pattern = s.to_lowercase();
+                if !pattern.chars().all(|c| c.is_ascii_lowercase() || c == '*' || c == '?') {
+                    return Err(format!("Invalid language pattern \"{}\"", s));
+                }
+                Value::Str(pattern)
+            }
+            (Field::Purposes, Opcode::In, Value::Str(s))
+            | (Field::Purposes, Opcode::NotIn, Value::Str(s)) => Value::Int(purpose_id(&s)?),
+            (Field::Purposes, Opcode::In, Value::Int(v))
+            | (Field::Purposes, Opcode::NotIn, Value::Int(v))
+            | (Field::Consents, Opcode::In, Value::Int(v))
+            | (Field::Consents, Opcode::NotIn, Value::Int(v)) => Value::Int(v),
+            (Field::ConsentLanguage, _, _) | (Field::Purposes, _, _) | (Field::Consents, _, _) => {
+                return Err(format!("Operator {} cannot be applied to {}", opcode, field))
+            }
+            (_, Opcode::OneOf, Value::Vec(v))
+            | (_, Opcode::NotOneOf, Value::Vec(v)) => Value::Vec(v),
+            (_, Opcode::Gt, Value::Int(v))
+            | (_, Opcode::Ge, Value::Int(v))
+            | (_, Opcode::Lt, Value::Int(v))
+            | (_, Opcode::Le, Value::Int(v))
+            | (_, Opcode::Eq, Value::Int(v))
+            | (_, Opcode::Ne, Value::Int(v)) => Value::Int(v),
+            _ => return Err(format!("Operator {} cannot be applied to {}", opcode, field)),
+        };
+        Ok(Expr::Op(field, opcode, value))
+    }
+
+    /// Builds a comparison between two operands, checking that they are of the same type.
+    ///
+    /// A plain field compared with a number is built with `Expr::op`, so timestamps may still be
+    /// compared with raw deciseconds.
+    pub fn compare(l: Operand, opcode: Opcode, r: Operand) -> Result<Expr, String> {
+        match opcode {
+            Opcode::Gt | Opcode::Ge | Opcode::Lt | Opcode::Le | Opcode::Eq | Opcode::Ne => (),
+            _ => return Err(format!("Operator {} cannot be applied to numbers", opcode)),
+        }
+        if let (&Operand::Field(field), &Operand::Int(v)) = (&l, &r) {
+            return Expr::op(field, opcode, Value::Int(v));
+        }
+        match (l.ty()?, r.ty()?) {
+            (lt, rt) if lt == rt => (),
+            (Type::Timestamp, Type::Number) if r.is_literal() => (),
+            (Type::Number, Type::Timestamp) if l.is_literal() => (),
+            (lt, rt) => return Err(format!("Cannot compare {} with {}", lt, rt)),
+        }
+        Ok(Expr::Compare(l, opcode, r))
+    }
+
+    /// Builds a comparison with a string, which is only possible for a plain field.
+    pub fn compare_str(l: Operand, opcode: Opcode, s: String) -> Result<Expr, String> {
+        match l {
+            Operand::Field(field) => Expr::op(field, opcode, Value::Str(s)),
+            l => Err(format!("Cannot compare {} with \"{}\"", l, s)),
+        }
+    }
+
+    /// Rebuilds the expression through the checked constructors, so that one assembled by hand
+    /// is held to the same rules as a parsed one.
+    pub fn checked(self) -> Result<Expr, String> {
+        Ok(match self {
+            Expr::And(l, r) => Expr::And(Box::new(l.checked()?), Box::new(r.checked()?)),
+            Expr::Or(l, r) => Expr::Or(Box::new(l.checked()?), Box::new(r.checked()?)),
+            Expr::Not(e) => Expr::Not(Box::new(e.checked()?)),
+            Expr::Op(field, opcode, value) => Expr::op(field, opcode, value)?,
+            Expr::Compare(l, opcode, r) => Expr::compare(l, opcode, r)?,
+        })
+    }
+
+    /// Builds `field has all [...]` (or `has any`) as a chain of single comparisons.
+    ///
+    /// `hasn't` negates the whole test, so `hasn't any` means none of the values are present.
+    pub fn quantified(
+        field: Field,
+        opcode: Opcode,
+        all: bool,
+        values: Value,
+    ) -> Result<Expr, String> {
+        let values = match values {
+            Value::Vec(v) => v.into_iter().map(Value::Int).collect::<Vec<_>>(),
+            Value::StrVec(v) => v.into_iter().map(Value::Str).collect(),
+            _ => return Err("all and any must be followed by a list".to_string()),
+        };
+        let negate = match opcode {
+            Opcode::In => false,
+            Opcode::NotIn => true,
+            _ => return Err("all and any can only be used with has and hasn't".to_string()),
+        };
+        let mut tests = values
+            .into_iter()
+            .map(|value| Expr::op(field, Opcode::In, value))
+            .collect::<Result<Vec<_>, _>>()?;
+        if tests.is_empty() {
+            return Err("all and any must be followed by at least one value".to_string());
+        }
+        // Joined pairwise into a balanced tree, so that long lists stay shallow
+        while tests.len() > 1 {
+            let mut joined = Vec::with_capacity(tests.len().div_ceil(2));
+            let mut tests_iter = tests.into_iter();
+            while let Some(l) = tests_iter.next() {
+                joined.push(match tests_iter.next() {
+                    Some(r) if all => Expr::And(Box::new(l), Box::new(r)),
+                    Some(r) => Expr::Or(Box::new(l), Box::new(r)),
+                    None => l,
+                });
+            }
+            tests = joined;
+        }
+        let expr = tests.pop().expect("there is at least one test");
+        Ok(if negate { Expr::Not(Box::new(expr)) } else { expr })
+    }
+
+    /// How deeply the expression nests, found without recursing so that any expression can be
+    /// measured.
+    pub fn depth(&self) -> usize {
+        let mut deepest = 0;
+        let mut stack = vec![(self, 1)];
+        while let Some((expr, depth)) = stack.pop() {
+            deepest = cmp::max(deepest, depth);
+            match expr {
+                Expr::And(l, r) | Expr::Or(l, r) => {
+                    stack.push((l, depth + 1));
+                    stack.push((r, depth + 1));
+                }
+                Expr::Not(e) => stack.push((e, depth + 1)),
+                Expr::Op(..) => (),
+                Expr::Compare(l, _, r) => {
+                    deepest = cmp::max(deepest, depth + cmp::max(l.depth(), r.depth()));
+                }
+            }
+        }
+        deepest
+    }
+
+    /// Tests a consent string against the expression. A comparison whose operator does not apply
+    /// to its operands, which the checked constructors never build, is false.
+    pub fn eval(&self, gdpr: &ConsentString) -> bool {
+        match self {
+            Expr::And(left, right) => left.eval(gdpr) && right.eval(gdpr),
+            Expr::Or(left, right) => left.eval(gdpr) || right.eval(gdpr),
+            Expr::Op(field, opcode, val) => opcode.check(field.get(gdpr), val).unwrap_or(false),
+            Expr::Compare(l, opcode, r) => match (l.eval(gdpr), r.eval(gdpr)) {
+                (Some(l), Some(r)) => opcode.compare(l, r).unwrap_or(false),
+                _ => false,
+            },
+            Expr::Not(expr) => !expr.eval(gdpr),
+        }
+    }
+
+    /// Evaluates the expression, describing the result of every node on its own line. Unlike
+    /// `eval`, both sides of `&` and `|` are always evaluated.
+    pub fn explain(&self, gdpr: &ConsentString) -> String {
+        let mut out = String::new();
+        self.explain_into(gdpr, 0, &mut out);
+        out
+    }
+
+    fn explain_into(&self, gdpr: &ConsentString, depth: usize, out: &mut String) -> bool {
+        let indent = "  ".repeat(depth);
+        let mut children = String::new();
+        let (label, result) = match self {
+            Expr::And(left, right) => {
+                let l = left.explain_into(gdpr, depth + 1, &mut children);
+                let r = right.explain_into(gdpr, depth + 1, &mut children);
+                ("&".to_string(), l && r)
+            }
+            Expr::Or(left, right) => {
+                let l = left.explain_into(gdpr, depth + 1, &mut children);
+                let r = right.explain_into(gdpr, depth + 1, &mut children);
+                ("|".to_string(), l || r)
+            }
+            Expr::Not(expr) => (
+                "!".to_string(),
+                !expr.explain_into(gdpr, depth + 1, &mut children),
+            ),
+            Expr::Op(field, opcode, val) => {
+                let actual = field.get(gdpr);
+                let label = format!("{} ({} is {})", self, field, Summary(&actual));
+                let result = opcode.check(actual, val).unwrap_or(false);
+                (label, result)
+            }
+            Expr::Compare(l, opcode, r) => {
+                let (lv, rv) = (l.eval(gdpr), r.eval(gdpr));
+                let values: Vec<_> = [(l, lv), (r, rv)]
+                    .iter()
+                    .filter(|(operand, _)| !operand.is_literal())
+                    .map(|(operand, value)| match value {
+                        Some(value) => format!("{} is {}", operand, value),
+                        None => format!("{} is undefined", operand),
+                    })
+                    .collect();
+                let label = format!("{} ({})", self, values.join(", "));
+                let result = match (lv, rv) {
+                    (Some(l), Some(r)) => opcode.compare(l, r).unwrap_or(false),
+                    _ => false,
+                };
+                (label, result)
+            }
+        };
+        out.push_str(&format!("{}{} => {}\n", indent, label, result));
+        out.push_str(&children);
+        result
+    }
+}
+
+impl Field {
+    pub fn get(&self, gdpr: &ConsentString) -> Value {
+        match self {
+            Field::Version => Value::Int(gdpr.version as u64),
+            Field::Created => Value::Int(deciseconds(&gdpr.created)),
+            Field::LastUpdated => Value::Int(deciseconds(&gdpr.last_updated)),
+            Field::CmpId => Value::Int(gdpr.cmp_id as u64),
+            Field::CmpVersion => Value::Int(gdpr.cmp_version as u64),
+            Field::ConsentScreen => Value::Int(gdpr.consent_screen as u64),
+            Field::ConsentLanguage => Value::Str(gdpr.consent_language.iter().collect()),
+            Field::VendorListVersion => Value::Int(gdpr.vendor_list_version as u64),
+            Field::Purposes => Value::Vec(
+                gdpr.purposes_allowed
+                    .entries()
+                    .map(|&(id, _, _)| id as u64)
+                    .collect(),
+            ),
+            Field::MaxVendorId => Value::Int(gdpr.max_vendor_id as u64),
+            Field::Consents => Value::Vec(
+                gdpr.vendor_consents
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(id, &value)| {
+                        if id > 0 && value {
+                            Some(id as u64)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Operand {
+    /// Builds `count(field)`, which only makes sense for the list fields.
+    pub fn count(field: Field) -> Result<Operand, String> {
+        field.countable().map(Operand::Count)
+    }
+
+    /// How deeply the operand nests, found without recursing like `Expr::depth`.
+    pub fn depth(&self) -> usize {
+        let mut deepest = 0;
+        let mut stack = vec![(self, 1)];
+        while let Some((operand, depth)) = stack.pop() {
+            deepest = cmp::max(deepest, depth);
+            match operand {
+                Operand::Add(l, r)
+                | Operand::Sub(l, r)
+                | Operand::Mul(l, r)
+                | Operand::Div(l, r) => {
+                    stack.push((l, depth + 1));
+                    stack.push((r, depth + 1));
+                }
+                _ => (),
+            }
+        }
+        deepest
+    }
+
+    fn is_literal(&self) -> bool {
+        matches!(self, Operand::Int(_) | Operand::Duration(_))
+    }
+
+    fn ty(&self) -> Result<Type, String> {
+        let (l, r) = match self {
+            Operand::Field(Field::Created) | Operand::Field(Field::LastUpdated) => {
+                return Ok(Type::Timestamp)
+            }
+            Operand::Field(field @ Field::Purposes) | Operand::Field(field @ Field::Consents) => {
+                return Err(format!(
+                    "{} is not a number; did you mean count({})?",
+                    field, field
+                ))
+            }
+            Operand::Field(Field::ConsentLanguage) => {
+                return Err("consentlanguage is not a number".to_string())
+            }
+            // Arithmetic is done in i64
+            Operand::Int(i) | Operand::Duration(i) if i64::try_from(*i).is_err() => {
+                return Err(format!("{} is too large", self))
+            }
+            Operand::Count(field) => return Operand::count(*field).map(|_| Type::Number),
+            Operand::Field(_) | Operand::Int(_) => return Ok(Type::Number),
+            Operand::Duration(_) => return Ok(Type::Duration),
+            Operand::Div(_, r) if r.is_zero() => return Err("Division by zero".to_string()),
+            Operand::Add(l, r) | Operand::Sub(l, r) | Operand::Mul(l, r) | Operand::Div(l, r) => {
+                (l.ty()?, r.ty()?)
+            }
+        };
+        let ty = match (self, l, r) {
+            (Operand::Add(..), Type::Timestamp, Type::Duration)
+            | (Operand::Add(..), Type::Duration, Type::Timestamp)
+            | (Operand::Sub(..), Type::Timestamp, Type::Duration) => Some(Type::Timestamp),
+            (Operand::Sub(..), Type::Timestamp, Type::Timestamp)
+            | (Operand::Mul(..), Type::Duration, Type::Number)
+            | (Operand::Mul(..), Type::Number, Type::Duration)
+            | (Operand::Div(..), Type::Duration, Type::Number) => Some(Type::Duration),
+            (Operand::Div(..), Type::Duration, Type::Duration) => Some(Type::Number),
+            (Operand::Add(..), l, r) | (Operand::Sub(..), l, r)
+                if l == r && l != Type::Timestamp =>
+            {
+                Some(l)
+            }
+            (Operand::Mul(..), Type::Number, Type::Number)
+            | (Operand::Div(..), Type::Number, Type::Number) => Some(Type::Number),
+            _ => None,
+        };
+        ty.ok_or_else(|| format!("Cannot apply {} to {} and {}", self.symbol(), l, r))
+    }
+
+    fn is_zero(&self) -> bool {
+        matches!(self, Operand::Int(0) | Operand::Duration(0))
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Operand::Add(..) => "+",
+            Operand::Sub(..) => "-",
+            Operand::Mul(..) => "*",
+            Operand::Div(..) => "/",
+            _ => "",
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Operand::Add(..) | Operand::Sub(..) => 1,
+            Operand::Mul(..) | Operand::Div(..) => 2,
+            _ => 3,
+        }
+    }
+
+    /// Computes the operand's value, or `None` on overflow or division by zero. Never allocates.
+    pub fn eval(&self, gdpr: &ConsentString) -> Option<i64> {
+        match self {
+            Operand::Field(field) => match field.get(gdpr) {
+                Value::Int(i) => i64::try_from(i).ok(),
+                _ => None,
+            },
+            Operand::Count(field) => field.count(gdpr).map(|n| n as i64),
+            Operand::Int(i) | Operand::Duration(i) => i64::try_from(*i).ok(),
+            Operand::Add(l, r) => l.eval(gdpr)?.checked_add(r.eval(gdpr)?),
+            Operand::Sub(l, r) => l.eval(gdpr)?.checked_sub(r.eval(gdpr)?),
+            Operand::Mul(l, r) => l.eval(gdpr)?.checked_mul(r.eval(gdpr)?),
+            Operand::Div(l, r) => l.eval(gdpr)?.checked_div(r.eval(gdpr)?),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Field(field) => write!(f, "{}", field),
+            Operand::Count(field) => write!(f, "count({})", field),
+            Operand::Int(i) => write!(f, "{}", i),
+            Operand::Duration(d) => {
+                let (n, unit) = [
+                    (6_048_000, "week"),
+                    (864_000, "day"),
+                    (36_000, "hour"),
+                    (600, "minute"),
+                    (10, "second"),
+                ].iter()
+                    .find(|&&(size, _)| d % size == 0)
+                    .map(|&(size, unit)| (d / size, unit))
+                    .unwrap_or((*d, "decisecond"));
+                write!(f, "{} {}{}", n, unit, if n == 1 { "" } else { "s" })
+            }
+            Operand::Add(l, r) | Operand::Sub(l, r) | Operand::Mul(l, r) | Operand::Div(l, r) => {
+                if l.precedence() < self.precedence() {
+                    write!(f, "({})", l)?;
+                } else {
+                    write!(f, "{}", l)?;
+                }
+                write!(f, " {} ", self.symbol())?;
+                if r.precedence() <= self.precedence() {
+                    write!(f, "({})", r)
+                } else {
+                    write!(f, "{}", r)
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Type::Number => "a number",
+            Type::Timestamp => "a timestamp",
+            Type::Duration => "a duration",
+        })
+    }
+}
+
+/// Prints an expression back as filter source. Named predicates and vendor names have already
+/// been resolved, so the output uses plain ids.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Or(l, r) => match **r {
+                Expr::Or(..) => write!(f, "{} | ({})", l, r),
+                _ => write!(f, "{} | {}", l, r),
+            },
+            Expr::And(l, r) => {
+                match **l {
+                    Expr::Or(..) => write!(f, "({})", l)?,
+                    _ => write!(f, "{}", l)?,
+                }
+                match **r {
+                    Expr::Or(..) | Expr::And(..) => write!(f, " & ({})", r),
+                    _ => write!(f, " & {}", r),
+                }
+            }
+            Expr::Not(e) => match **e {
+                Expr::Or(..) | Expr::And(..) => write!(f, "!({})", e),
+                _ => write!(f, "!{}", e),
+            },
+            Expr::Op(field, opcode, value) => write!(f, "{} {} {}", field, opcode, Literal(value)),
+            Expr::Compare(l, opcode, r) => write!(f, "{} {} {}", l, opcode, r),
+        }
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Opcode::Gt => ">",
+            Opcode::Ge => ">=",
+            Opcode::Lt => "<",
+            Opcode::Le => "<=",
+            Opcode::Eq => "=",
+            Opcode::Ne => "!=",
+            Opcode::In => "has",
+            Opcode::NotIn => "hasn't",
+            Opcode::OneOf => "in",
+            Opcode::NotOneOf => "not in",
+            Opcode::Like => "like",
+            Opcode::NotLike => "not like",
+        })
+    }
+}
+
+/// Formats a value as it would be written in filter source
+struct Literal<'a>(&'a Value);
+
+impl<'a> fmt::Display for Literal<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Str(s) => write!(f, "\"{}\"", s),
+            Value::Vec(v) => write!(
+                f,
+                "[{}]",
+                v.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(", ")
+            ),
+            Value::StrVec(v) => write!(
+                f,
+                "[{}]",
+                v.iter()
+                    .map(|s| format!("\"{}\"", s))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// Formats a field's value for `Expr::explain`, collapsing runs of ids into ranges
+struct Summary<'a>(&'a Value);
+
+impl<'a> fmt::Display for Summary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Value::Vec(v) if v.is_empty() => f.write_str("empty"),
+            Value::Vec(v) => f.write_str(&ranges(v.iter().map(|&id| id as usize))),
+            value => write!(f, "{}", value),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Vec(v) => write!(
+                f,
+                "{}",
+                v.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(",")
+            ),
+            Value::StrVec(v) => write!(f, "{}", v.join(",")),
+        }
+    }
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "a number",
+            Value::Str(_) => "a string",
+            Value::Vec(_) => "a list of numbers",
+            Value::StrVec(_) => "a list of strings",
+        }
+    }
+}
+
+impl Opcode {
+    /// Applies one of the ordering operators; any other operator is an error.
+    pub fn compare<T: Ord>(&self, l: T, r: T) -> Result<bool, String> {
+        match self {
+            Opcode::Gt => Ok(l > r),
+            Opcode::Ge => Ok(l >= r),
+            Opcode::Lt => Ok(l < r),
+            Opcode::Le => Ok(l <= r),
+            Opcode::Eq => Ok(l == r),
+            Opcode::Ne => Ok(l != r),
+            _ => Err(format!("Operator {} cannot be applied to numbers", self)),
+        }
+    }
+
+    /// Applies the operator to a field's value and a literal, failing if the operator does not
+    /// apply to them.
+    pub fn check(&self, l: Value, r: &Value) -> Result<bool, String> {
+        let result = match (&l, r) {
+            (&Value::Int(l), &Value::Int(r)) => return self.compare(l, r),
+            (Value::Int(l), Value::Vec(r)) => match self {
+                Opcode::OneOf => Some(r.contains(l)),
+                Opcode::NotOneOf => Some(!r.contains(l)),
+                _ => None,
+            },
+            (Value::Vec(v), Value::Int(r)) => match self {
+                Opcode::In => Some(v.contains(r)),
+                Opcode::NotIn => Some(!v.contains(r)),
+                _ => None,
+            },
+            (Value::Str(l), Value::Str(r)) => match self {
+                Opcode::Eq => Some(l == r),
+                Opcode::Ne => Some(l != r),
+                Opcode::Like => Some(glob_match(r.as_bytes(), l.as_bytes())),
+                Opcode::NotLike => Some(!glob_match(r.as_bytes(), l.as_bytes())),
+                _ => None,
+            },
+            (Value::Str(l), Value::StrVec(r)) => match self {
+                Opcode::OneOf => Some(r.contains(l)),
+                Opcode::NotOneOf => Some(!r.contains(l)),
+                _ => None,
+            },
+            _ => None,
+        };
+        result.ok_or_else(|| {
+            format!(
+                "Operator {} cannot be applied to {} and {}",
+                self,
+                l.kind(),
+                r.kind()
+            )
+        })
+    }
+}
+
+/// Matches `text` against a pattern where `*` matches any run of characters and `?` matches any
+/// single character.
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+        Some((&p, rest)) => match text.split_first() {
+            Some((&t, text)) if p == b'?' || p == t => glob_match(rest, text),
+            _ => false,
+        },
+    }
+}
+
+/// Resolves a vendor name against the vendor list, failing if it matches no vendor or several.
+pub fn vendor_id(gvl: Option<&VendorList>, name: &str) -> Result<u64, String> {
+    let gvl = gvl.ok_or_else(|| format!("Cannot look up vendor \"{}\" without a vendor list (--gvl)", name))?;
+    let vendors = gvl.vendors_named(name);
+    match vendors.len() {
+        0 => Err(format!("No vendor named \"{}\"", name)),
+        1 => Ok(vendors[0].id as u64),
+        _ => Err(format!(
+            "Vendor name \"{}\" is ambiguous; it matches {}",
+            name,
+            vendors
+                .iter()
+                .map(|vendor| format!("\"{}\" ({})", vendor.name, vendor.id))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Maps a short purpose name to its purpose id.
+fn purpose_id(name: &str) -> Result<u64, String> {
+    match name.to_lowercase().as_str() {
+        "storage" | "storageandaccess" => Ok(1),
+        "personalisation" | "personalization" => Ok(2),
+        "ads" | "adselection" => Ok(3),
+        "content" | "contentdelivery" => Ok(4),
+        "measurement" => Ok(5),
+        _ => Err(format!("Unknown purpose \"{}\"", name)),
+    }
+}
+
+/// Lowercases a language literal, checking that it is a two-letter ISO 639-1 code.
+fn language_code(s: &str) -> Result<String, String> {
+    let code = s.to_lowercase();
+    if ISO_639_1.contains(&code.as_str()) {
+        Ok(code)
+    } else {
+        Err(format!("\"{}\" is not an ISO 639-1 language code", s))
+    }
+}
+
+#[rustfmt::skip]
+static ISO_639_1: [&str; 184] = [
+    "aa", "ab", "ae", "af", "ak", "am", "an", "ar", "as", "av", "ay", "az", "ba", "be", "bg", "bh",
+    "bi", "bm", "bn", "bo", "br", "bs", "ca", "ce", "ch", "co", "cr", "cs", "cu", "cv", "cy", "da",
+    "de", "dv", "dz", "ee", "el", "en", "eo", "es", "et", "eu", "fa", "ff", "fi", "fj", "fo", "fr",
+    "fy", "ga", "gd", "gl", "gn", "gu", "gv", "ha", "he", "hi", "ho", "hr", "ht", "hu", "hy", "hz",
+    "ia", "id", "ie", "ig", "ii", "ik", "io", "is", "it", "iu", "ja", "jv", "ka", "kg", "ki", "kj",
+    "kk", "kl", "km", "kn", "ko", "kr", "ks", "ku", "kv", "kw", "ky", "la", "lb", "lg", "li", "ln",
+    "lo", "lt", "lu", "lv", "mg", "mh", "mi", "mk", "ml", "mn", "mr", "ms", "mt", "my", "na", "nb",
+    "nd", "ne", "ng", "nl", "nn", "no", "nr", "nv", "ny", "oc", "oj", "om", "or", "os", "pa", "pi",
+    "pl", "ps", "pt", "qu", "rm", "rn", "ro", "ru", "rw", "sa", "sc", "sd", "se", "sg", "si", "sk",
+    "sl", "sm", "sn", "so", "sq", "sr", "ss", "st", "su", "sv", "sw", "ta", "te", "tg", "th", "ti",
+    "tk", "tl", "tn", "to", "tr", "ts", "tt", "tw", "ty", "ug", "uk", "ur", "uz", "ve", "vi", "vo",
+    "wa", "wo", "xh", "yi", "yo", "za", "zh", "zu",
+];
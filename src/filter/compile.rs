@@ -0,0 +1,237 @@
+use filter::ast::{glob_match, Expr, Opcode, Operand, Value};
+use {ConsentString, Field, Purpose, Purposes};
+
+/// A filter expression compiled into a flat list of nodes.
+///
+/// Unlike `Expr::eval`, evaluating a `Program` never allocates: vendor tests look up a single
+/// consent bit, purpose tests check a precomputed flag, and constant subexpressions are folded
+/// away at compile time. The children of `&` and `|` chains are evaluated cheapest first.
+#[derive(Debug)]
+pub struct Program {
+    nodes: Vec<Node>,
+    root: usize,
+}
+
+#[derive(Debug)]
+enum Node {
+    Const(bool),
+    Not(usize),
+    All(Vec<usize>),
+    Any(Vec<usize>),
+    Int(Field, Opcode, u64),
+    IntOneOf(Field, Vec<u64>, bool),
+    Language(Opcode, [u8; 2]),
+    LanguageOneOf(Vec<[u8; 2]>, bool),
+    LanguageLike(Vec<u8>, bool),
+    Purpose(Purposes, bool),
+    Vendor(usize, bool),
+    Compare(Operand, Opcode, Operand),
+}
+
+impl Program {
+    pub fn new(expr: &Expr) -> Program {
+        let mut program = Program {
+            nodes: vec![],
+            root: 0,
+        };
+        program.root = program.compile(expr);
+        program
+    }
+
+    pub fn eval(&self, gdpr: &ConsentString) -> bool {
+        self.eval_node(self.root, gdpr)
+    }
+
+    fn eval_node(&self, idx: usize, gdpr: &ConsentString) -> bool {
+        match self.nodes[idx] {
+            Node::Const(b) => b,
+            Node::Not(child) => !self.eval_node(child, gdpr),
+            Node::All(ref children) => children.iter().all(|&child| self.eval_node(child, gdpr)),
+            Node::Any(ref children) => children.iter().any(|&child| self.eval_node(child, gdpr)),
+            Node::Int(field, opcode, r) => {
+                opcode.compare(int_field(field, gdpr), r).unwrap_or(false)
+            }
+            Node::IntOneOf(field, ref values, expect) => {
+                values.binary_search(&int_field(field, gdpr)).is_ok() == expect
+            }
+            Node::Language(opcode, code) => (language(gdpr) == code) == (opcode == Opcode::Eq),
+            Node::LanguageOneOf(ref codes, expect) => codes.contains(&language(gdpr)) == expect,
+            Node::LanguageLike(ref pattern, expect) => {
+                glob_match(pattern, &language(gdpr)) == expect
+            }
+            Node::Purpose(purpose, expect) => gdpr.purposes_allowed.contains(purpose) == expect,
+            Node::Vendor(id, expect) => {
+                gdpr.vendor_consents.get(id).cloned().unwrap_or(false) == expect
+            }
+            Node::Compare(ref l, opcode, ref r) => match (l.eval(gdpr), r.eval(gdpr)) {
+                (Some(l), Some(r)) => opcode.compare(l, r).unwrap_or(false),
+                _ => false,
+            },
+        }
+    }
+
+    fn push(&mut self, node: Node) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    fn constant(&self, idx: usize) -> Option<bool> {
+        match self.nodes[idx] {
+            Node::Const(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// A rough estimate of how expensive a node is to evaluate
+    fn cost(&self, idx: usize) -> usize {
+        match self.nodes[idx] {
+            Node::Const(_) => 0,
+            Node::Not(child) => self.cost(child),
+            Node::All(ref children) | Node::Any(ref children) => {
+                children.iter().map(|&child| self.cost(child)).sum()
+            }
+            Node::IntOneOf(_, ref values, _) => 1 + values.len() / 8,
+            Node::LanguageOneOf(ref codes, _) => 1 + codes.len() / 8,
+            Node::LanguageLike(ref pattern, _) => 1 + pattern.len(),
+            Node::Int(..) | Node::Language(..) | Node::Purpose(..) | Node::Vendor(..) => 1,
+            Node::Compare(ref l, _, ref r) => 1 + operand_cost(l) + operand_cost(r),
+        }
+    }
+
+    fn compile(&mut self, expr: &Expr) -> usize {
+        match expr {
+            Expr::And(..) => {
+                let mut children = vec![];
+                self.flatten(expr, true, &mut children);
+                self.chain(children, true)
+            }
+            Expr::Or(..) => {
+                let mut children = vec![];
+                self.flatten(expr, false, &mut children);
+                self.chain(children, false)
+            }
+            Expr::Not(expr) => {
+                let child = self.compile(expr);
+                match self.nodes[child] {
+                    Node::Const(b) => self.push(Node::Const(!b)),
+                    Node::Not(grandchild) => grandchild,
+                    _ => self.push(Node::Not(child)),
+                }
+            }
+            Expr::Op(field, opcode, value) => {
+                let node = compile_op(*field, *opcode, value);
+                self.push(node)
+            }
+            Expr::Compare(l, opcode, r) => self.push(Node::Compare(l.clone(), *opcode, r.clone())),
+        }
+    }
+
+    /// Collects the operands of a chain of `&` (or `|`) into a single list.
+    fn flatten(&mut self, expr: &Expr, and: bool, children: &mut Vec<usize>) {
+        match (expr, and) {
+            (Expr::And(l, r), true) | (Expr::Or(l, r), false) => {
+                self.flatten(l, and, children);
+                self.flatten(r, and, children);
+            }
+            _ => children.push(self.compile(expr)),
+        }
+    }
+
+    fn chain(&mut self, children: Vec<usize>, and: bool) -> usize {
+        // An `&` is decided by any false operand, and an `|` by any true one
+        if children.iter().any(|&child| self.constant(child) == Some(!and)) {
+            return self.push(Node::Const(!and));
+        }
+        let mut children: Vec<_> = children
+            .into_iter()
+            .filter(|&child| self.constant(child).is_none())
+            .collect();
+        match children.len() {
+            0 => self.push(Node::Const(and)),
+            1 => children[0],
+            _ => {
+                children.sort_by_key(|&child| self.cost(child));
+                self.push(if and {
+                    Node::All(children)
+                } else {
+                    Node::Any(children)
+                })
+            }
+        }
+    }
+}
+
+fn compile_op(field: Field, opcode: Opcode, value: &Value) -> Node {
+    match (field, opcode, value) {
+        (Field::Purposes, _, &Value::Int(id)) => {
+            let expect = opcode == Opcode::In;
+            match Purpose::from_id(id) {
+                Some(purpose) => Node::Purpose(purpose.into(), expect),
+                None => Node::Const(!expect),
+            }
+        }
+        (Field::Consents, _, &Value::Int(id)) => {
+            let expect = opcode == Opcode::In;
+            if id == 0 || id > u16::MAX as u64 {
+                Node::Const(!expect)
+            } else {
+                Node::Vendor(id as usize, expect)
+            }
+        }
+        (Field::ConsentLanguage, Opcode::Like, Value::Str(pattern)) => {
+            Node::LanguageLike(pattern.as_bytes().to_vec(), true)
+        }
+        (Field::ConsentLanguage, Opcode::NotLike, Value::Str(pattern)) => {
+            Node::LanguageLike(pattern.as_bytes().to_vec(), false)
+        }
+        (Field::ConsentLanguage, _, Value::Str(code)) => match language_literal(code) {
+            Some(code) => Node::Language(opcode, code),
+            // No consent string has a language that long
+            None => Node::Const(opcode == Opcode::Ne),
+        },
+        (Field::ConsentLanguage, _, Value::StrVec(codes)) => Node::LanguageOneOf(
+            codes.iter().filter_map(|code| language_literal(code)).collect(),
+            opcode == Opcode::OneOf,
+        ),
+        (_, _, Value::Vec(values)) => {
+            let mut values = values.clone();
+            values.sort();
+            Node::IntOneOf(field, values, opcode == Opcode::OneOf)
+        }
+        (_, _, &Value::Int(r)) => Node::Int(field, opcode, r),
+        _ => unreachable!("Expr::op accepted {:?} {:?} {:?}", field, opcode, value),
+    }
+}
+
+fn operand_cost(operand: &Operand) -> usize {
+    match operand {
+        // Counting consents walks every vendor
+        Operand::Count(Field::Consents) => 8,
+        Operand::Count(_) => 1,
+        Operand::Field(_) | Operand::Int(_) | Operand::Duration(_) => 0,
+        Operand::Add(l, r) | Operand::Sub(l, r) | Operand::Mul(l, r) | Operand::Div(l, r) => {
+            operand_cost(l) + operand_cost(r)
+        }
+    }
+}
+
+fn language_literal(code: &str) -> Option<[u8; 2]> {
+    match code.as_bytes() {
+        &[a, b] => Some([a, b]),
+        _ => None,
+    }
+}
+
+fn language(gdpr: &ConsentString) -> [u8; 2] {
+    [
+        gdpr.consent_language[0] as u8,
+        gdpr.consent_language[1] as u8,
+    ]
+}
+
+fn int_field(field: Field, gdpr: &ConsentString) -> u64 {
+    match field.get(gdpr) {
+        Value::Int(i) => i,
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,136 @@
+use filter::ast::Expr;
+use filter::grammar::ModuleParser;
+use filter::lexer::{position, Lexer};
+use lalrpop_util::ParseError;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use VendorList;
+
+/// State shared by every file that makes up a filter program: the vendor list used to resolve
+/// vendor names, the predicates defined so far, the files currently being read, and the files
+/// already included. Each compile starts with a fresh context.
+pub(crate) struct Context<'a> {
+    pub gvl: Option<&'a VendorList>,
+    definitions: RefCell<HashMap<String, Expr>>,
+    files: RefCell<Vec<PathBuf>>,
+    included: RefCell<HashSet<PathBuf>>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(gvl: Option<&'a VendorList>) -> Context<'a> {
+        Context {
+            gvl,
+            definitions: RefCell::new(HashMap::new()),
+            files: RefCell::new(vec![]),
+            included: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Parses a program, which must end in an expression.
+    pub(crate) fn parse(&self, source: &str) -> Result<Expr, String> {
+        self.parse_module(source)?
+            .ok_or_else(|| "Expected an expression after the definitions".to_string())
+    }
+
+    /// Parses a program read from a file; `include`s are resolved relative to it.
+    pub(crate) fn parse_file(&self, path: &Path) -> Result<Expr, String> {
+        let source = self.enter(path)?;
+        let rv = self.parse(&source);
+        self.files.borrow_mut().pop();
+        rv.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub(crate) fn define(&self, name: &str, expr: Expr) -> Result<(), String> {
+        let mut definitions = self.definitions.borrow_mut();
+        if definitions.contains_key(name) {
+            return Err(format!("{} is already defined", name));
+        }
+        definitions.insert(name.to_string(), expr);
+        Ok(())
+    }
+
+    pub(crate) fn lookup(&self, name: &str) -> Result<Expr, String> {
+        self.definitions
+            .borrow()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("{} is not defined", name))
+    }
+
+    /// Reads the definitions from another file into this context. A file that has already been
+    /// included is skipped, so two files may both include a third.
+    pub(crate) fn include(&self, path: &str) -> Result<(), String> {
+        let path = match self.files.borrow().last().and_then(|file| file.parent()) {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        };
+        let canonical = canonicalize(&path)?;
+        if self.included.borrow().contains(&canonical) {
+            return Ok(());
+        }
+        let source = self.enter(&path)?;
+        let rv = self.parse_module(&source);
+        self.files.borrow_mut().pop();
+        match rv {
+            Ok(None) => {
+                self.included.borrow_mut().insert(canonical);
+                Ok(())
+            }
+            Ok(Some(_)) => Err(format!(
+                "{}: included files may only contain definitions",
+                path.display()
+            )),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    fn enter(&self, path: &Path) -> Result<String, String> {
+        let canonical = canonicalize(path)?;
+        if self.files.borrow().contains(&canonical) {
+            return Err(format!("{} includes itself", path.display()));
+        }
+        let mut source = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut source))
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        self.files.borrow_mut().push(canonical);
+        Ok(source)
+    }
+
+    fn parse_module(&self, source: &str) -> Result<Option<Expr>, String> {
+        ModuleParser::new()
+            .parse(self, Lexer::new(source))
+            .map_err(|e| match e {
+                ParseError::InvalidToken { location } => {
+                    format!("Invalid token at {}", position(source, location))
+                }
+                ParseError::UnrecognizedToken {
+                    token: Some((start, token, _)),
+                    expected,
+                } => format!(
+                    "Unexpected `{}` at {}; expected one of {}",
+                    token,
+                    position(source, start),
+                    expected.join(", ")
+                ),
+                ParseError::UnrecognizedToken {
+                    token: None,
+                    expected,
+                } => format!(
+                    "Unexpected end of input; expected one of {}",
+                    expected.join(", ")
+                ),
+                ParseError::ExtraToken {
+                    token: (start, token, _),
+                } => format!("Unexpected `{}` at {}", token, position(source, start)),
+                ParseError::User { error } => error,
+            })
+    }
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf, String> {
+    fs::canonicalize(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))
+}
@@ -0,0 +1,237 @@
+use std::fmt;
+
+/// A token of the filter language. Keywords and symbols are spelled out in `KEYWORDS` and
+/// `SYMBOLS`, which `Lexer` and `Display` share.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tok<'input> {
+    Version,
+    Created,
+    LastUpdated,
+    CmpId,
+    CmpVersion,
+    ConsentScreen,
+    ConsentLanguage,
+    VendorListVersion,
+    Purposes,
+    MaxVendorId,
+    Consents,
+    Let,
+    Include,
+    All,
+    Any,
+    Count,
+    Vendor,
+    Has,
+    Hasnt,
+    In,
+    Not,
+    Like,
+    Decisecond,
+    Deciseconds,
+    Second,
+    Seconds,
+    Minute,
+    Minutes,
+    Hour,
+    Hours,
+    Day,
+    Days,
+    Week,
+    Weeks,
+    Comma,
+    Semicolon,
+    Pipe,
+    Ampersand,
+    Bang,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    Eq,
+    Ne,
+    Num(&'input str),
+    Ident(&'input str),
+    /// A double-quoted string, without its quotes
+    Quoted(&'input str),
+}
+
+const KEYWORDS: &[(&str, Tok<'static>)] = &[
+    ("version", Tok::Version),
+    ("created", Tok::Created),
+    ("lastupdated", Tok::LastUpdated),
+    ("cmpid", Tok::CmpId),
+    ("cmpversion", Tok::CmpVersion),
+    ("consentscreen", Tok::ConsentScreen),
+    ("consentlanguage", Tok::ConsentLanguage),
+    ("vendorlistversion", Tok::VendorListVersion),
+    ("purposes", Tok::Purposes),
+    ("maxvendorid", Tok::MaxVendorId),
+    ("consents", Tok::Consents),
+    ("let", Tok::Let),
+    ("include", Tok::Include),
+    ("all", Tok::All),
+    ("any", Tok::Any),
+    ("count", Tok::Count),
+    ("vendor", Tok::Vendor),
+    ("has", Tok::Has),
+    ("hasn't", Tok::Hasnt),
+    ("in", Tok::In),
+    ("not", Tok::Not),
+    ("like", Tok::Like),
+    ("decisecond", Tok::Decisecond),
+    ("deciseconds", Tok::Deciseconds),
+    ("second", Tok::Second),
+    ("seconds", Tok::Seconds),
+    ("minute", Tok::Minute),
+    ("minutes", Tok::Minutes),
+    ("hour", Tok::Hour),
+    ("hours", Tok::Hours),
+    ("day", Tok::Day),
+    ("days", Tok::Days),
+    ("week", Tok::Week),
+    ("weeks", Tok::Weeks),
+];
+
+/// Two-character symbols come first, so that `>=` isn't read as `>` then `=`
+const SYMBOLS: &[(&str, Tok<'static>)] = &[
+    (">=", Tok::Ge),
+    ("<=", Tok::Le),
+    ("!=", Tok::Ne),
+    (",", Tok::Comma),
+    (";", Tok::Semicolon),
+    ("|", Tok::Pipe),
+    ("&", Tok::Ampersand),
+    ("!", Tok::Bang),
+    ("(", Tok::LParen),
+    (")", Tok::RParen),
+    ("[", Tok::LBracket),
+    ("]", Tok::RBracket),
+    ("+", Tok::Plus),
+    ("-", Tok::Minus),
+    ("*", Tok::Star),
+    ("/", Tok::Slash),
+    (">", Tok::Gt),
+    ("<", Tok::Lt),
+    ("=", Tok::Eq),
+];
+
+impl<'input> fmt::Display for Tok<'input> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Tok::Num(s) | Tok::Ident(s) => write!(f, "{}", s),
+            Tok::Quoted(s) => write!(f, "\"{}\"", s),
+            ref tok => {
+                let &(text, _) = KEYWORDS
+                    .iter()
+                    .chain(SYMBOLS)
+                    .find(|(_, t)| t == tok)
+                    .expect("every other token is a keyword or symbol");
+                write!(f, "{}", text)
+            }
+        }
+    }
+}
+
+/// Splits a filter program into tokens, skipping whitespace and comments, which run from `#` or
+/// `//` to the end of the line. Tokens are located by their byte offsets into the program.
+pub struct Lexer<'input> {
+    source: &'input str,
+    offset: usize,
+}
+
+impl<'input> Lexer<'input> {
+    pub fn new(source: &'input str) -> Lexer<'input> {
+        Lexer { source, offset: 0 }
+    }
+
+    /// Moves past whitespace and comments.
+    fn skip(&mut self) {
+        loop {
+            let rest = &self.source[self.offset..];
+            let trimmed = rest.trim_start();
+            let rest = if trimmed.starts_with('#') || trimmed.starts_with("//") {
+                trimmed.find('\n').map_or("", |end| &trimmed[end..])
+            } else {
+                trimmed
+            };
+            if rest.len() == self.source.len() - self.offset {
+                return;
+            }
+            self.offset = self.source.len() - rest.len();
+        }
+    }
+}
+
+impl<'input> Iterator for Lexer<'input> {
+    type Item = Result<(usize, Tok<'input>, usize), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.skip();
+        let start = self.offset;
+        let rest = &self.source[start..];
+        let c = rest.chars().next()?;
+        let len = if c.is_ascii_digit() {
+            rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len())
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            if &rest[..len] == "hasn" && rest[len..].starts_with("'t") {
+                len + 2
+            } else {
+                len
+            }
+        } else if c == '"' {
+            match rest[1..].find('"') {
+                Some(end) => end + 2,
+                None => {
+                    return Some(Err(format!(
+                        "Unterminated string at {}",
+                        position(self.source, start)
+                    )))
+                }
+            }
+        } else {
+            match SYMBOLS.iter().find(|&&(text, _)| rest.starts_with(text)) {
+                Some(&(text, _)) => text.len(),
+                None => {
+                    return Some(Err(format!(
+                        "Unexpected character `{}` at {}",
+                        c,
+                        position(self.source, start)
+                    )))
+                }
+            }
+        };
+        self.offset += len;
+        let text = &rest[..len];
+        let tok = if c.is_ascii_digit() {
+            Tok::Num(text)
+        } else if c == '"' {
+            Tok::Quoted(&text[1..len - 1])
+        } else {
+            KEYWORDS
+                .iter()
+                .chain(SYMBOLS)
+                .find(|&&(t, _)| t == text)
+                .map_or(Tok::Ident(text), |&(_, tok)| tok)
+        };
+        Some(Ok((start, tok, self.offset)))
+    }
+}
+
+/// Describes a byte offset into `source` as a line and column, both counted from 1.
+pub fn position(source: &str, offset: usize) -> String {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before[before.rfind('\n').map_or(0, |i| i + 1)..].chars().count() + 1;
+    format!("line {}, column {}", line, column)
+}
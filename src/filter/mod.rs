@@ -0,0 +1,247 @@
+//! A small expression language for selecting consent strings, as used by the `filter` binary.
+//!
+//! ```
+//! use gdpr_consent_string::filter::Filter;
+//! use gdpr_consent_string::ConsentString;
+//!
+//! let filter = Filter::compile("cmpid = 7 & consents has all [1, 2] & consentlanguage like \"e*\"").unwrap();
+//! let gdpr = ConsentString::parse("BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA").unwrap();
+//! assert!(filter.matches(&gdpr));
+//! ```
+
+mod ast;
+mod compile;
+mod context;
+// Generated from grammar.lalrpop, so its style isn't ours to lint
+#[allow(clippy::all, unused_parens)]
+mod grammar;
+mod lexer;
+
+pub use self::ast::{Expr, Opcode, Operand, Value, MAX_DEPTH};
+pub use Field;
+
+use self::ast::nested;
+use self::compile::Program;
+use self::context::Context;
+use std::fmt;
+use std::path::Path;
+use {ConsentString, VendorList};
+
+/// A filter expression that failed to parse or type-check
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl ::std::error::Error for Error {}
+
+/// A parsed filter, compiled for fast evaluation
+#[derive(Debug)]
+pub struct Filter {
+    expr: Expr,
+    program: Program,
+}
+
+impl Filter {
+    /// Parses a filter program. Vendor names cannot be used without a vendor list; see
+    /// `compile_with`.
+    pub fn compile(source: &str) -> Result<Filter, Error> {
+        Filter::compile_with(None, source)
+    }
+
+    /// Parses a filter program, resolving vendor names with `gvl`.
+    pub fn compile_with(gvl: Option<&VendorList>, source: &str) -> Result<Filter, Error> {
+        Context::new(gvl).parse(source).map(Filter::new).map_err(Error)
+    }
+
+    /// Parses a filter program from a file, resolving `include`s relative to it.
+    pub fn compile_file(gvl: Option<&VendorList>, path: &Path) -> Result<Filter, Error> {
+        Context::new(gvl).parse_file(path).map(Filter::new).map_err(Error)
+    }
+
+    /// Compiles an expression built by hand, checking it as if it had been parsed.
+    pub fn from_expr(expr: Expr) -> Result<Filter, Error> {
+        let depth = expr.depth();
+        nested(expr, depth)
+            .and_then(|(expr, _)| expr.checked())
+            .map(Filter::new)
+            .map_err(Error)
+    }
+
+    fn new(expr: Expr) -> Filter {
+        let program = Program::new(&expr);
+        Filter { expr, program }
+    }
+
+    pub fn matches(&self, gdpr: &ConsentString) -> bool {
+        self.program.eval(gdpr)
+    }
+
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+}
+
+/// Prints the filter as canonical source
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.expr.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filter::*;
+
+    #[test]
+    fn compiled_matches_tree_walker() {
+        let gdpr = ConsentString::parse("BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA").unwrap();
+        for (source, expected) in &[
+            ("cmpid = 7", true),
+            ("consents has 9", false),
+            ("consents hasn't any [9, 10]", false),
+            ("purposes has all [storage, ads] & !(purposes has measurement)", true),
+            ("consentlanguage in [\"EN\", fr] | version > 1", true),
+            ("consentlanguage not like \"e?\"", false),
+            ("cmpid not in [1, 2] & (consents has 0 | maxvendorid >= 2011)", true),
+            ("let a = cmpid = 7; let b = cmpversion = 2; a & !b", true),
+            ("lastupdated = created", true),
+            ("lastupdated - created < 1 second & created + 2 weeks > lastupdated", true),
+            ("maxvendorid > count(consents) * 2", false),
+            ("count(consents) = maxvendorid - 1 & count(purposes) / 2 = 1", true),
+            ("# comment\ncmpid = 7 // trailing\n& version = 1 #", true),
+        ] {
+            let filter = Filter::compile(source).unwrap();
+            assert_eq!(filter.matches(&gdpr), *expected, "{}", source);
+            assert_eq!(filter.expr().eval(&gdpr), *expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for source in &[
+            "cmpid = 7 & (version > 1 | consents has 3)",
+            "!(purposes has 1 & purposes has 2) | consentlanguage not in [\"de\", \"fr\"]",
+            "cmpid in [1, 2] & (consentlanguage like \"e*\" & maxvendorid < 10)",
+            "lastupdated - created > 30 days & maxvendorid * 2 - (cmpid - 1) >= count(consents)",
+        ] {
+            let printed = Filter::compile(source).unwrap().to_string();
+            assert_eq!(&printed, source);
+            assert_eq!(Filter::compile(&printed).unwrap().to_string(), printed);
+        }
+    }
+
+    #[test]
+    fn explain() {
+        let gdpr = ConsentString::parse("BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA").unwrap();
+        let filter = Filter::compile("cmpid = 7 & !consents has 9").unwrap();
+        assert_eq!(
+            filter.expr().explain(&gdpr),
+            "& => true\n  cmpid = 7 (cmpid is 7) => true\n  ! => true\n    \
+             consents has 9 (consents is 1-8,10-2011) => false\n"
+        );
+    }
+
+    #[test]
+    fn includes() {
+        use std::fs;
+        let dir = ::std::env::temp_dir().join(format!("gdpr-includes-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, source) in &[
+            ("common.gdpr", "let seven = cmpid = 7;"),
+            ("a.gdpr", "include \"common.gdpr\"; let a = seven;"),
+            ("b.gdpr", "include \"common.gdpr\"; let b = !seven;"),
+            ("main.gdpr", "include \"a.gdpr\"; include \"b.gdpr\"; a & !b"),
+            ("loop.gdpr", "include \"loop.gdpr\"; cmpid = 7"),
+        ] {
+            fs::write(dir.join(name), source).unwrap();
+        }
+        let gdpr = ConsentString::parse("BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA").unwrap();
+        let filter = Filter::compile_file(None, &dir.join("main.gdpr")).unwrap();
+        assert!(filter.matches(&gdpr));
+        assert!(Filter::compile_file(None, &dir.join("main.gdpr")).is_ok());
+        assert!(Filter::compile_file(None, &dir.join("loop.gdpr")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn vendor_names() {
+        let gvl = VendorList::from_reader(
+            &br#"{"vendorListVersion": 8, "vendors": [{"id": 9, "name": "Example Ads"}]}"#[..],
+        ).unwrap();
+        let gdpr = ConsentString::parse("BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA").unwrap();
+        // Each compile starts afresh, so the same definitions can be compiled again
+        for _ in 0..2 {
+            let filter =
+                Filter::compile_with(Some(&gvl), "let ads = consents has vendor(\"example\"); !ads")
+                    .unwrap();
+            assert!(filter.matches(&gdpr));
+        }
+    }
+
+    #[test]
+    fn nesting() {
+        let gdpr = ConsentString::parse("BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA").unwrap();
+        let deepest = format!("{}cmpid = 7", "!".repeat(MAX_DEPTH - 1));
+        let filter = Filter::compile(&deepest).unwrap();
+        assert_eq!(filter.matches(&gdpr), MAX_DEPTH % 2 == 1);
+        assert_eq!(filter.expr().eval(&gdpr), MAX_DEPTH % 2 == 1);
+        assert_eq!(Filter::compile(&filter.to_string()).unwrap().to_string(), deepest);
+        filter.expr().explain(&gdpr);
+        assert!(Filter::compile(&format!("!{}", deepest)).is_err());
+        assert!(Filter::compile(&format!("{}cmpid = 7", "!".repeat(50_000))).is_err());
+        assert!(Filter::compile(&format!("{}1 > 0", "1 + ".repeat(50_000))).is_err());
+        assert!(Filter::compile(&format!("{}cmpid = 7", "cmpid = 7 | ".repeat(50_000))).is_err());
+
+        // Long lists are joined into shallow trees
+        let ids: Vec<_> = (1..=5000).map(|id| id.to_string()).collect();
+        let filter = Filter::compile(&format!("consents has any [{}]", ids.join(", "))).unwrap();
+        assert!(filter.expr().depth() < 20);
+        assert!(filter.matches(&gdpr));
+
+        let mut expr = Expr::Op(Field::CmpId, Opcode::Eq, Value::Int(7));
+        for _ in 0..MAX_DEPTH {
+            expr = Expr::Not(Box::new(expr));
+        }
+        assert!(Filter::from_expr(expr).is_err());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Filter::compile("consentlanguage = xx").is_err());
+        assert!(Filter::compile("consents has vendor(\"Google\")").is_err());
+        assert!(Filter::compile("cmpid = ").is_err());
+        assert!(Filter::compile("lastupdated - created > 30").is_err());
+        assert!(Filter::compile("created + created > lastupdated").is_err());
+        assert!(Filter::compile("count(consents) > consents").is_err());
+        assert!(Filter::compile("cmpid = 18446744073709551616").is_err());
+        assert!(Filter::compile("lastupdated - created > 18446744073709551615 weeks").is_err());
+        assert!(Filter::compile("cmpid + 9223372036854775808 > 1").is_err());
+        assert!(Filter::compile("lastupdated - created > 1000000000000000000 seconds").is_err());
+        assert!(Filter::compile("cmpid < 9223372036854775808").is_ok());
+        assert_eq!(
+            Filter::compile("# comment\ncmpid > 3 &\n  @").err().unwrap().to_string(),
+            "Unexpected character `@` at line 3, column 3"
+        );
+        assert!(Filter::from_expr(Expr::Op(Field::CmpId, Opcode::Like, Value::Int(1))).is_err());
+        assert!(Filter::from_expr(Expr::Op(Field::Consents, Opcode::Gt, Value::Int(1))).is_err());
+        assert!(Expr::quantified(Field::Consents, Opcode::In, true, Value::Vec(vec![])).is_err());
+        assert!(Filter::from_expr(Expr::Compare(
+            Operand::Count(Field::CmpId),
+            Opcode::Gt,
+            Operand::Int(1)
+        )).is_err());
+        assert_eq!(
+            Operand::count(Field::CmpId).unwrap_err(),
+            "Cannot count cmpid"
+        );
+        assert_eq!(
+            Expr::op(Field::ConsentLanguage, Opcode::OneOf, Value::Vec(vec![])).unwrap_err(),
+            "Operator in cannot be applied to consentlanguage"
+        );
+    }
+}
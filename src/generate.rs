@@ -0,0 +1,149 @@
+//! Random but valid consent strings, for load tests and for fuzzing the code that consumes them.
+
+use chrono::{DateTime, TimeZone, Utc};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::cmp;
+use std::ops::RangeInclusive;
+use {ConsentString, Error, Purposes, VendorEncoding};
+
+/// The distributions a `Generator` draws each field from. Integer fields are drawn uniformly
+/// from their range, clamped to what the field can hold.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub versions: RangeInclusive<u8>,
+    /// `last_updated` is drawn between `created` and the end of this range
+    pub created: RangeInclusive<DateTime<Utc>>,
+    pub cmp_ids: RangeInclusive<u16>,
+    pub cmp_versions: RangeInclusive<u16>,
+    pub consent_screens: RangeInclusive<u8>,
+    /// Chosen from uniformly; each must be two lowercase ASCII letters
+    pub languages: Vec<[char; 2]>,
+    pub vendor_list_versions: RangeInclusive<u16>,
+    /// The chance that each of purposes 1 to 5 is allowed, clamped to between 0 and 1
+    pub purpose_probabilities: [f64; 5],
+    pub max_vendor_ids: RangeInclusive<u16>,
+    /// The chance that each vendor has consent, clamped to between 0 and 1
+    pub consent_density: f64,
+    pub encoding: VendorEncoding,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            versions: 1..=1,
+            created: Utc.with_ymd_and_hms(2018, 5, 25, 0, 0, 0).unwrap()
+                ..=Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap(),
+            cmp_ids: 1..=300,
+            cmp_versions: 1..=20,
+            consent_screens: 0..=5,
+            languages: vec![['e', 'n'], ['f', 'r'], ['d', 'e'], ['i', 't'], ['e', 's']],
+            vendor_list_versions: 1..=200,
+            purpose_probabilities: [0.8; 5],
+            max_vendor_ids: 1..=700,
+            consent_density: 0.8,
+            encoding: VendorEncoding::Shortest,
+        }
+    }
+}
+
+/// Generates consent strings from a `Config`. The same seed and config always produce the same
+/// strings.
+#[derive(Debug)]
+pub struct Generator {
+    config: Config,
+    rng: StdRng,
+}
+
+impl Generator {
+    /// Fails if one of the languages isn't two lowercase ASCII letters, or a probability is NaN.
+    pub fn new(config: Config, seed: u64) -> Result<Generator, Error> {
+        let languages_valid = config
+            .languages
+            .iter()
+            .all(|language| language.iter().all(|c| c.is_ascii_lowercase()));
+        if !languages_valid {
+            return Err(Error::Invalid("consent language"));
+        }
+        if config.consent_density.is_nan() || config.purpose_probabilities.iter().any(|p| p.is_nan())
+        {
+            return Err(Error::Invalid("probability"));
+        }
+        Ok(Generator {
+            config,
+            rng: StdRng::seed_from_u64(seed),
+        })
+    }
+
+    pub fn consent_string(&mut self) -> ConsentString {
+        let config = &self.config;
+        let rng = &mut self.rng;
+        let (start, end) = (
+            deciseconds(config.created.start()),
+            deciseconds(config.created.end()),
+        );
+        let created = uniform(rng, start, end);
+        let last_updated = uniform(rng, created, end);
+        let purposes_allowed = config
+            .purpose_probabilities
+            .iter()
+            .enumerate()
+            .filter(|&(_, &p)| rng.gen_bool(p.clamp(0.0, 1.0)))
+            .fold(Purposes::none(), |acc, (bit, _)| {
+                acc | Purposes::from_raw(1 << bit)
+            });
+        let max_vendor_id = field(rng, &config.max_vendor_ids, 16) as u16;
+        let density = config.consent_density.clamp(0.0, 1.0);
+        let vendor_consents = (0..=max_vendor_id)
+            .map(|id| id > 0 && rng.gen_bool(density))
+            .collect();
+        ConsentString {
+            version: field(rng, &config.versions, 6) as u8,
+            created: ::time(created).expect("created is clamped to 36 bits"),
+            last_updated: ::time(last_updated).expect("last updated is clamped to 36 bits"),
+            cmp_id: field(rng, &config.cmp_ids, 12) as u16,
+            cmp_version: field(rng, &config.cmp_versions, 12) as u16,
+            consent_screen: field(rng, &config.consent_screens, 6) as u8,
+            consent_language: config.languages.choose(rng).cloned().unwrap_or(['e', 'n']),
+            vendor_list_version: field(rng, &config.vendor_list_versions, 12) as u16,
+            purposes_allowed,
+            max_vendor_id,
+            vendor_consents,
+        }
+    }
+}
+
+/// Yields encoded strings, endlessly.
+impl Iterator for Generator {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let encoding = self.config.encoding;
+        let encoded = self.consent_string().encode_with(encoding);
+        Some(encoded.expect("fields are drawn to fit, and Generator::new checks the languages"))
+    }
+}
+
+/// Draws from `range`, clamped to what fits in `bits` bits.
+fn field<T: Copy + Into<u64>>(rng: &mut StdRng, range: &RangeInclusive<T>, bits: u8) -> u64 {
+    let max = (1 << bits) - 1;
+    uniform(
+        rng,
+        cmp::min((*range.start()).into(), max),
+        cmp::min((*range.end()).into(), max),
+    )
+}
+
+fn uniform(rng: &mut StdRng, low: u64, high: u64) -> u64 {
+    if low >= high {
+        low
+    } else {
+        rng.gen_range(low, high + 1)
+    }
+}
+
+/// Timestamps are stored in 36 bits of deciseconds since the epoch
+fn deciseconds(time: &DateTime<Utc>) -> u64 {
+    cmp::min(::deciseconds(time), (1 << 36) - 1)
+}
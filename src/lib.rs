@@ -1,9 +1,50 @@
+//! Parsing and encoding of IAB GDPR consent strings.
+//!
+//! The default features also build the command-line tools, which pull in `structopt` and
+//! `rand`. Library users should turn them off and add back `filter` or `generate` as needed:
+//!
+//! ```toml
+//! [dependencies]
+//! gdpr-consent-string = { version = "0.1", default-features = false, features = ["filter"] }
+//! ```
+
 #[macro_use]
 extern crate bitmask;
 extern crate chrono;
+#[cfg(feature = "filter")]
+extern crate lalrpop_util;
+#[cfg(test)]
+extern crate proptest;
+#[cfg(feature = "generate")]
+extern crate rand;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+#[cfg(feature = "cli")]
+#[macro_use]
+extern crate structopt;
 
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, Utc};
+use std::cmp;
+use std::fmt;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod cli;
 mod consts;
+#[cfg(any(feature = "cli", feature = "filter"))]
+mod field;
+#[cfg(feature = "filter")]
+pub mod filter;
+#[cfg(feature = "generate")]
+pub mod generate;
+pub mod vendor_list;
+
+#[cfg(feature = "generate")]
+pub use generate::Generator;
+#[cfg(any(feature = "cli", feature = "filter"))]
+pub use field::Field;
+pub use vendor_list::{Vendor, VendorList};
 
 bitmask! {
     pub mask Purposes: u32 where flags Purpose {
@@ -51,19 +92,57 @@ impl Purposes {
     fn from_raw(raw: u32) -> Purposes {
         Purposes { mask: raw }
     }
+
+    /// The entries of `PURPOSES` for the allowed purposes, in id order
+    #[cfg(any(feature = "cli", feature = "filter"))]
+    pub(crate) fn entries(self) -> impl Iterator<Item = &'static (u8, Purpose, &'static str)> {
+        PURPOSES
+            .iter()
+            .filter(move |&&(_, purpose, _)| self.contains(purpose))
+    }
 }
 
-fn decode(c: char) -> u8 {
+impl Purpose {
+    /// The purpose with the given id in `PURPOSES`
+    #[cfg(any(feature = "cli", feature = "filter"))]
+    pub(crate) fn from_id(id: u64) -> Option<Purpose> {
+        PURPOSES
+            .iter()
+            .find(|&&(purpose_id, _, _)| purpose_id as u64 == id)
+            .map(|&(_, purpose, _)| purpose)
+    }
+}
+
+/// Each purpose with its id, as used in JSON and filter expressions, and its name for display
+#[cfg(any(feature = "cli", feature = "filter"))]
+pub(crate) const PURPOSES: [(u8, Purpose, &str); 5] = [
+    (1, Purpose::StorageAndAccess, "Storage and access"),
+    (2, Purpose::Personalization, "Personalization"),
+    (3, Purpose::AdSelection, "Ad selection"),
+    (4, Purpose::ContentDelivery, "Content delivery"),
+    (5, Purpose::Measurement, "Measurement"),
+];
+
+/// The value of a URL-safe Base64 character, or `None` if it isn't one
+pub(crate) fn sextet(c: char) -> Option<u8> {
     match c {
-        'A'...'Z' => c as u8 - 'A' as u8,
-        'a'...'z' => c as u8 - 'a' as u8 + 26,
-        '0'...'9' => c as u8 - '0' as u8 + 52,
-        '-' => 62,
-        '_' => 63,
-        _ => panic!("Character {} is not a valid Base64 character", c),
+        'A'..='Z' => Some(c as u8 - b'A'),
+        'a'..='z' => Some(c as u8 - b'a' + 26),
+        '0'..='9' => Some(c as u8 - b'0' + 52),
+        '-' => Some(62),
+        '_' => Some(63),
+        _ => None,
     }
 }
 
+fn decode(c: char) -> u8 {
+    sextet(c).unwrap_or_else(|| panic!("Character {} is not a valid Base64 character", c))
+}
+
+fn is_base64(c: char) -> bool {
+    sextet(c).is_some()
+}
+
 fn take_6<T: Iterator<Item = char>>(it: &mut T) -> Option<u8> {
     it.next().map(decode)
 }
@@ -92,11 +171,15 @@ fn take_36<T: Iterator<Item = char>>(it: &mut T) -> Option<u64> {
     )
 }
 
-fn language_code<T: Iterator<Item = char>>(it: &mut T) -> Option<[char; 2]> {
+fn language_code<T: Iterator<Item = char>>(it: &mut T) -> Result<[char; 2], Error> {
     use consts::LETTERS;
-    let a = it.next().map(decode)?;
-    let b = it.next().map(decode)?;
-    Some([LETTERS[a as usize], LETTERS[b as usize]])
+    let truncated = Error::Truncated("consent language");
+    let a = it.next().map(decode).ok_or_else(|| truncated.clone())?;
+    let b = it.next().map(decode).ok_or(truncated)?;
+    match (LETTERS.get(a as usize), LETTERS.get(b as usize)) {
+        (Some(&a), Some(&b)) => Ok([a, b]),
+        _ => Err(Error::Invalid("consent language")),
+    }
 }
 
 fn purpose<T: Iterator<Item = char>>(it: &mut T) -> Option<u32> {
@@ -113,7 +196,139 @@ fn purpose<T: Iterator<Item = char>>(it: &mut T) -> Option<u32> {
     )
 }
 
-#[derive(Debug)]
+/// Deciseconds since the epoch, the unit of the timestamps in consent strings. Earlier times are
+/// clamped to zero.
+pub(crate) fn deciseconds(time: &DateTime<Utc>) -> u64 {
+    cmp::max(
+        time.timestamp() * 10 + (time.timestamp_subsec_millis() / 100) as i64,
+        0,
+    ) as u64
+}
+
+/// The inverse of `deciseconds`, or `None` if the time is too far in the future to represent.
+/// Any 36-bit timestamp can be represented.
+pub(crate) fn time(deciseconds: u64) -> Option<DateTime<Utc>> {
+    DateTime::<Utc>::from_timestamp(
+        (deciseconds / 10) as i64,
+        ((deciseconds % 10) * 100_000_000) as u32,
+    )
+}
+
+/// Formats ascending ids as a compact list of ranges, such as `1-8,10-2011`.
+#[cfg(any(feature = "cli", feature = "filter"))]
+pub(crate) fn ranges<I: IntoIterator<Item = usize>>(ids: I) -> String {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for id in ids {
+        match ranges.last_mut() {
+            Some(range) if range.1 + 1 == id => range.1 = id,
+            _ => ranges.push((id, id)),
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Length in bits of the range encoding of `entries`, after the encoding type
+fn range_len(entries: &[(u16, u16)]) -> usize {
+    1 + 12
+        + entries
+            .iter()
+            .map(|&(start, end)| if start == end { 17 } else { 33 })
+            .sum::<usize>()
+}
+
+/// The reason a consent string could not be decoded or encoded
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The string contains a character outside the URL-safe Base64 alphabet
+    InvalidCharacter { character: char, position: usize },
+    /// The string ended while the named field was being read
+    Truncated(&'static str),
+    /// The named field holds a value the format does not allow, such as a consent language
+    /// letter past `z`, a range entry above the max vendor id, or a value too wide for its bits
+    Invalid(&'static str),
+    /// In strict mode, the string breaks the spec in a way lenient mode would only warn about
+    Nonconforming(Warning),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidCharacter {
+                character,
+                position,
+            } => write!(
+                f,
+                "Character {:?} at position {} is not a valid Base64 character",
+                character, position
+            ),
+            Error::Truncated(field) => write!(f, "String ends before {} could be read", field),
+            Error::Invalid(field) => write!(f, "The {} is not valid", field),
+            Error::Nonconforming(warning) => warning.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A departure from the IAB spec that lenient parsing accepts
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// There are more characters than the fields and their padding need
+    TrailingData,
+    /// The bits padding the string out to a whole number of bytes are not all zero
+    NonZeroPadding,
+    /// The named timestamp is zero
+    ZeroTimestamp(&'static str),
+    UpdatedBeforeCreated,
+    /// The vendor bitfield ends before `max_vendor_id`; the missing vendors are taken not to
+    /// have consent
+    ShortBitField,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::TrailingData => f.write_str("There is data after the end of the string"),
+            Warning::NonZeroPadding => f.write_str("The padding bits are not all zero"),
+            Warning::ZeroTimestamp(field) => write!(f, "The {} time is zero", field),
+            Warning::UpdatedBeforeCreated => {
+                f.write_str("The string was last updated before it was created")
+            }
+            Warning::ShortBitField => {
+                f.write_str("The vendor bitfield ends before the max vendor id")
+            }
+        }
+    }
+}
+
+/// How `ConsentString::parse_with` treats strings that decode but break the spec
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ParseOptions {
+    /// Reject such strings with `Error::Nonconforming`, instead of returning warnings
+    pub strict: bool,
+}
+
+impl ParseOptions {
+    pub fn strict() -> ParseOptions {
+        ParseOptions { strict: true }
+    }
+
+    pub fn lenient() -> ParseOptions {
+        ParseOptions { strict: false }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ConsentString {
     pub version: u8,
     pub created: DateTime<Utc>,
@@ -128,11 +343,13 @@ pub struct ConsentString {
     pub vendor_consents: Vec<bool>,
 }
 
+/// Reads fields of arbitrary width from URL-safe Base64
 #[derive(Debug)]
 pub(crate) struct BitDecoder<T> {
     base: T,
     offset: u8,
     leftover: u8,
+    read: usize,
 }
 
 impl<T: Iterator<Item = char> + std::fmt::Debug> BitDecoder<T> {
@@ -141,9 +358,11 @@ impl<T: Iterator<Item = char> + std::fmt::Debug> BitDecoder<T> {
             base: base.into_iter(),
             offset: 0,
             leftover: 0,
+            read: 0,
         }
     }
-    pub fn take(&mut self, n: u8) -> Option<usize> {
+    /// Reads the next `n` bits, most significant first. `n` may be at most 64.
+    pub fn take(&mut self, n: u8) -> Option<u64> {
         if self.offset == 0 {
             self.leftover = decode(self.base.next()?);
             self.offset = 6;
@@ -153,12 +372,14 @@ impl<T: Iterator<Item = char> + std::fmt::Debug> BitDecoder<T> {
             // We have enough left to provide this request
             let rv = (self.leftover & mask) >> (self.offset - n);
             self.offset -= n;
+            self.read += n as usize;
 
-            Some(rv as usize)
+            Some(rv as u64)
         } else {
             // We don't have enough. Take what we can first, and add the rest
             let missing = n - self.offset;
-            let rv = (self.leftover & mask) as usize;
+            let rv = (self.leftover & mask) as u64;
+            self.read += self.offset as usize;
             self.offset = 0;
             Some(rv << missing | self.take(missing)?)
         }
@@ -168,61 +389,292 @@ impl<T: Iterator<Item = char> + std::fmt::Debug> BitDecoder<T> {
     }
 }
 
+/// Reads all of `input` as `width`-bit fields, returning them XORed together. Lets the benchmarks
+/// time `BitDecoder::take` without it being public.
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub fn bench_take(input: &str, width: u8) -> u64 {
+    let mut bd = BitDecoder::new(input.chars());
+    let mut acc = 0;
+    while let Some(value) = bd.take(width) {
+        acc ^= value;
+    }
+    acc
+}
+
+/// The inverse of `BitDecoder`: packs fields into URL-safe Base64
+#[derive(Debug, Default)]
+pub(crate) struct BitEncoder {
+    out: String,
+    leftover: u8,
+    offset: u8,
+    len: usize,
+}
+
+impl BitEncoder {
+    pub fn put(&mut self, value: u64, n: u8) {
+        for bit in (0..n).rev() {
+            self.leftover = self.leftover << 1 | (value >> bit & 1) as u8;
+            self.offset += 1;
+            self.len += 1;
+            if self.offset == 6 {
+                self.out.push(ENCODE[self.leftover as usize] as char);
+                self.leftover = 0;
+                self.offset = 0;
+            }
+        }
+    }
+
+    pub fn put_bool(&mut self, value: bool) {
+        self.put(value as u64, 1);
+    }
+
+    /// Pads with zeros to a whole number of bytes, as the strings are Base64-encoded bytes.
+    pub fn finish(mut self) -> String {
+        while !self.len.is_multiple_of(8) {
+            self.put(0, 1);
+        }
+        if self.offset > 0 {
+            let n = 6 - self.offset;
+            self.put(0, n);
+        }
+        self.out
+    }
+}
+
+static ENCODE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// How `ConsentString::encode_with` writes vendor consents
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VendorEncoding {
+    /// Whichever of the bitfield and range encodings is shorter
+    Shortest,
+    BitField,
+    /// Falls back to a bitfield if there would be more than 4095 range entries
+    Range,
+}
+
 impl ConsentString {
+    /// Encodes the string, using whichever of the bitfield and range vendor encodings is shorter.
+    ///
+    /// Fails if a header field is too wide for its bits, a timestamp is before the epoch or
+    /// beyond 2^36 deciseconds after it, or the consent language isn't two lowercase ASCII
+    /// letters.
+    pub fn encode(&self) -> Result<String, Error> {
+        self.encode_with(VendorEncoding::Shortest)
+    }
+
+    /// Like `encode`, but with a choice of vendor encoding.
+    pub fn encode_with(&self, encoding: VendorEncoding) -> Result<String, Error> {
+        for &(name, value, bits) in &[
+            ("version", self.version as u64, 6),
+            ("CMP id", self.cmp_id as u64, 12),
+            ("CMP version", self.cmp_version as u64, 12),
+            ("consent screen", self.consent_screen as u64, 6),
+            ("vendor list version", self.vendor_list_version as u64, 12),
+        ] {
+            if value >= 1 << bits {
+                return Err(Error::Invalid(name));
+            }
+        }
+        for &(name, time) in &[("created", &self.created), ("last updated", &self.last_updated)] {
+            if time.timestamp() < 0 || deciseconds(time) >= 1 << 36 {
+                return Err(Error::Invalid(name));
+            }
+        }
+        if !self.consent_language.iter().all(|c| c.is_ascii_lowercase()) {
+            return Err(Error::Invalid("consent language"));
+        }
+        let mut be = BitEncoder::default();
+        be.put(self.version as u64, 6);
+        be.put(deciseconds(&self.created), 36);
+        be.put(deciseconds(&self.last_updated), 36);
+        be.put(self.cmp_id as u64, 12);
+        be.put(self.cmp_version as u64, 12);
+        be.put(self.consent_screen as u64, 6);
+        for &c in &self.consent_language {
+            be.put(c as u64 - 'a' as u64, 6);
+        }
+        be.put(self.vendor_list_version as u64, 12);
+        let purposes = (0..24).fold(0, |acc, bit| {
+            acc << 1 | (self.purposes_allowed.mask >> bit & 1) as u64
+        });
+        be.put(purposes, 24);
+        be.put(self.max_vendor_id as u64, 16);
+
+        let bitfield_len = self.max_vendor_id as usize;
+        let ranges = [false, true]
+            .iter()
+            .map(|&default| (default, self.range_entries(default)))
+            .min_by_key(|(_, entries)| range_len(entries))
+            .filter(|(_, entries)| {
+                entries.len() < 1 << 12 && match encoding {
+                    VendorEncoding::Shortest => range_len(entries) < bitfield_len,
+                    VendorEncoding::BitField => false,
+                    VendorEncoding::Range => true,
+                }
+            });
+        match ranges {
+            Some((default, entries)) => {
+                be.put_bool(true);
+                be.put_bool(default);
+                be.put(entries.len() as u64, 12);
+                for (start, end) in entries {
+                    be.put_bool(start != end);
+                    be.put(start as u64, 16);
+                    if start != end {
+                        be.put(end as u64, 16);
+                    }
+                }
+            }
+            None => {
+                be.put_bool(false);
+                for id in 1..=self.max_vendor_id {
+                    be.put_bool(self.has_consent(id));
+                }
+            }
+        }
+        Ok(be.finish())
+    }
+
+    /// The runs of vendors whose consent differs from `default`
+    fn range_entries(&self, default: bool) -> Vec<(u16, u16)> {
+        let mut entries: Vec<(u16, u16)> = vec![];
+        for id in (1..=self.max_vendor_id).filter(|&id| self.has_consent(id) != default) {
+            match entries.last_mut() {
+                Some(entry) if entry.1 + 1 == id => entry.1 = id,
+                _ => entries.push((id, id)),
+            }
+        }
+        entries
+    }
+
     pub fn parse(str: &str) -> Option<ConsentString> {
+        ConsentString::try_parse(str).ok()
+    }
+
+    /// Like `parse`, but reports why the string could not be decoded. Strings that break the
+    /// spec are parsed leniently, except that a short bitfield is still an error; see
+    /// `parse_with` to accept those too.
+    pub fn try_parse(str: &str) -> Result<ConsentString, Error> {
+        let (gdpr, warnings) = ConsentString::parse_with(str, &ParseOptions::lenient())?;
+        if warnings.contains(&Warning::ShortBitField) {
+            return Err(Error::Truncated("vendor consents"));
+        }
+        Ok(gdpr)
+    }
+
+    /// Parses a string, choosing whether departures from the spec are errors or warnings. In
+    /// lenient mode, whatever was wrong with the string is returned alongside it.
+    pub fn parse_with(
+        str: &str,
+        options: &ParseOptions,
+    ) -> Result<(ConsentString, Vec<Warning>), Error> {
+        let mut warnings = vec![];
+        let mut warn = |warning| {
+            if options.strict {
+                return Err(Error::Nonconforming(warning));
+            }
+            warnings.push(warning);
+            Ok(())
+        };
+        if let Some((position, character)) = str.char_indices().find(|&(_, c)| !is_base64(c)) {
+            return Err(Error::InvalidCharacter {
+                character,
+                position,
+            });
+        }
         let mut chars = str.chars();
-        let version = take_6(&mut chars)?;
-        let created = take_36(&mut chars)?;
-        let created = NaiveDateTime::from_timestamp(
-            (created / 10) as i64,
-            ((created % 10) * 100_000_000) as u32,
-        );
-        let created = DateTime::<Utc>::from_utc(created, Utc);
-        let last_updated = take_36(&mut chars)?;
-        let last_updated = NaiveDateTime::from_timestamp(
-            (last_updated / 10) as i64,
-            ((last_updated % 10) * 100_000_000) as u32,
-        );
-        let last_updated = DateTime::<Utc>::from_utc(last_updated, Utc);
-        let cmp_id = take_12(&mut chars)?;
-        let cmp_version = take_12(&mut chars)?;
-        let consent_screen = take_6(&mut chars)?;
+        let version = take_6(&mut chars).ok_or(Error::Truncated("version"))?;
+        let created = take_36(&mut chars).ok_or(Error::Truncated("created"))?;
+        if created == 0 {
+            warn(Warning::ZeroTimestamp("created"))?;
+        }
+        let created = time(created).expect("36-bit timestamps are always valid");
+        let last_updated = take_36(&mut chars).ok_or(Error::Truncated("last updated"))?;
+        if last_updated == 0 {
+            warn(Warning::ZeroTimestamp("last updated"))?;
+        }
+        let last_updated = time(last_updated).expect("36-bit timestamps are always valid");
+        if last_updated < created {
+            warn(Warning::UpdatedBeforeCreated)?;
+        }
+        let cmp_id = take_12(&mut chars).ok_or(Error::Truncated("CMP id"))?;
+        let cmp_version = take_12(&mut chars).ok_or(Error::Truncated("CMP version"))?;
+        let consent_screen = take_6(&mut chars).ok_or(Error::Truncated("consent screen"))?;
         let consent_language = language_code(&mut chars)?;
-        let vendor_list_version = take_12(&mut chars)?;
-        let purposes_allowed = Purposes::from_raw(purpose(&mut chars)?);
+        let vendor_list_version =
+            take_12(&mut chars).ok_or(Error::Truncated("vendor list version"))?;
+        let purposes_allowed = Purposes::from_raw(
+            purpose(&mut chars).ok_or(Error::Truncated("purposes allowed"))?,
+        );
+        // The header is read a whole character at a time, and Base64 characters are ASCII
+        let header = (str.len() - chars.as_str().len()) * 6;
         let mut bd = BitDecoder::new(chars);
-        let max_vendor_id = bd.take(16)? as u16;
+        let max_vendor_id = bd.take(16).ok_or(Error::Truncated("max vendor id"))? as u16;
 
-        let range_encoding = bd.take_bool()?;
+        let range_encoding = bd.take_bool().ok_or(Error::Truncated("encoding type"))?;
         let vendor_consents: Vec<bool> = if range_encoding {
-            let default_consent = bd.take_bool()?;
-            let mut consents = vec![default_consent; (max_vendor_id + 1) as usize];
-            let num_entries = bd.take(12)?;
+            let default_consent = bd.take_bool().ok_or(Error::Truncated("default consent"))?;
+            let mut consents = vec![default_consent; max_vendor_id as usize + 1];
+            let num_entries = bd.take(12).ok_or(Error::Truncated("number of entries"))?;
             for _ in 0..num_entries {
-                let range = bd.take_bool()?;
+                let range = bd.take_bool().ok_or(Error::Truncated("range entry"))?;
                 if range {
-                    let start_vendor_id = bd.take(16)? as usize;
-                    let end_vendor_id = bd.take(16)? as usize;
-                    for vendor_id in start_vendor_id..=end_vendor_id {
-                        consents[vendor_id] = !default_consent;
+                    let start_vendor_id =
+                        bd.take(16).ok_or(Error::Truncated("range entry"))? as usize;
+                    let end_vendor_id =
+                        bd.take(16).ok_or(Error::Truncated("range entry"))? as usize;
+                    let entries = consents
+                        .get_mut(start_vendor_id..=end_vendor_id)
+                        .ok_or(Error::Invalid("range entry"))?;
+                    for consent in entries {
+                        *consent = !default_consent;
                     }
                 } else {
-                    let vendor_id = bd.take(16)? as usize;
-                    consents[vendor_id] = !default_consent;
+                    let vendor_id = bd.take(16).ok_or(Error::Truncated("range entry"))? as usize;
+                    *consents
+                        .get_mut(vendor_id)
+                        .ok_or(Error::Invalid("range entry"))? = !default_consent;
                 }
             }
+            // There is no vendor 0, whatever the default or the entries say
+            consents[0] = false;
             consents
         } else {
-            let mut rv = Vec::with_capacity((max_vendor_id + 1) as usize);
+            let mut rv = Vec::with_capacity(max_vendor_id as usize + 1);
             rv.push(false);
             for _ in 0..max_vendor_id {
-                rv.push(bd.take_bool()?);
+                match bd.take_bool() {
+                    Some(consent) => rv.push(consent),
+                    None => {
+                        warn(Warning::ShortBitField)?;
+                        rv.resize(max_vendor_id as usize + 1, false);
+                        break;
+                    }
+                }
             }
             rv
         };
 
-        // chars.map(decode).for_each(|b| print!("{:06b}", b));
-        Some(ConsentString {
+        // The fields are padded with zeros to a whole number of bytes, and then of characters
+        let len = header + bd.read;
+        let padded = (len.div_ceil(8) * 8).div_ceil(6) * 6;
+        let mut padding = 0;
+        for _ in len..padded {
+            match bd.take(1) {
+                Some(bit) => padding |= bit,
+                None => break,
+            }
+        }
+        if padding != 0 {
+            warn(Warning::NonZeroPadding)?;
+        }
+        if bd.take(1).is_some() {
+            warn(Warning::TrailingData)?;
+        }
+
+        let consent_string = ConsentString {
             version,
             created,
             last_updated,
@@ -234,12 +686,121 @@ impl ConsentString {
             purposes_allowed,
             max_vendor_id,
             vendor_consents,
-        })
+        };
+        Ok((consent_string, warnings))
+    }
+}
+
+/// A single difference between two consent strings, as found by `ConsentString::diff`. Header
+/// changes hold the old value followed by the new one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Version(u8, u8),
+    Created(DateTime<Utc>, DateTime<Utc>),
+    LastUpdated(DateTime<Utc>, DateTime<Utc>),
+    CmpId(u16, u16),
+    CmpVersion(u16, u16),
+    ConsentScreen(u8, u8),
+    ConsentLanguage([char; 2], [char; 2]),
+    VendorListVersion(u16, u16),
+    MaxVendorId(u16, u16),
+    PurposesAdded(Purposes),
+    PurposesRemoved(Purposes),
+    /// Vendors that have consent in the new string but not the old one
+    VendorsGained(Vec<u16>),
+    VendorsLost(Vec<u16>),
+}
+
+impl ConsentString {
+    /// Whether the vendor has consent. Vendors beyond `max_vendor_id` never do.
+    pub fn has_consent(&self, vendor_id: u16) -> bool {
+        vendor_id > 0 && self.vendor_consents.get(vendor_id as usize) == Some(&true)
+    }
+
+    /// Lists what changed going from `self` to `other`, header fields first.
+    pub fn diff(&self, other: &ConsentString) -> Vec<Change> {
+        let mut changes = vec![];
+        macro_rules! header {
+            ($field:ident, $change:ident) => {
+                if self.$field != other.$field {
+                    changes.push(Change::$change(self.$field, other.$field));
+                }
+            };
+        }
+        header!(version, Version);
+        header!(created, Created);
+        header!(last_updated, LastUpdated);
+        header!(cmp_id, CmpId);
+        header!(cmp_version, CmpVersion);
+        header!(consent_screen, ConsentScreen);
+        header!(consent_language, ConsentLanguage);
+        header!(vendor_list_version, VendorListVersion);
+        header!(max_vendor_id, MaxVendorId);
+        let added = other.purposes_allowed & !self.purposes_allowed;
+        if !added.is_none() {
+            changes.push(Change::PurposesAdded(added));
+        }
+        let removed = self.purposes_allowed & !other.purposes_allowed;
+        if !removed.is_none() {
+            changes.push(Change::PurposesRemoved(removed));
+        }
+        let max = cmp::max(self.max_vendor_id, other.max_vendor_id);
+        let (gained, lost): (Vec<u16>, Vec<u16>) = (1..=max)
+            .filter(|&id| self.has_consent(id) != other.has_consent(id))
+            .partition(|&id| other.has_consent(id));
+        if !gained.is_empty() {
+            changes.push(Change::VendorsGained(gained));
+        }
+        if !lost.is_empty() {
+            changes.push(Change::VendorsLost(lost));
+        }
+        changes
+    }
+
+    /// The purposes and vendors consented to in both strings.
+    ///
+    /// Header fields are taken from `self`, except that `last_updated` is the later of the two
+    /// and `max_vendor_id` covers the vendors of both strings.
+    pub fn intersection(&self, other: &ConsentString) -> ConsentString {
+        self.merge(other, self.purposes_allowed & other.purposes_allowed, |a, b| a && b)
+    }
+
+    /// The purposes and vendors consented to in either string. Header fields are chosen as for
+    /// `intersection`.
+    pub fn union(&self, other: &ConsentString) -> ConsentString {
+        self.merge(other, self.purposes_allowed | other.purposes_allowed, |a, b| a || b)
+    }
+
+    fn merge<F: Fn(bool, bool) -> bool>(
+        &self,
+        other: &ConsentString,
+        purposes_allowed: Purposes,
+        combine: F,
+    ) -> ConsentString {
+        let max_vendor_id = cmp::max(self.max_vendor_id, other.max_vendor_id);
+        let mut vendor_consents = vec![false];
+        vendor_consents.extend(
+            (1..=max_vendor_id).map(|id| combine(self.has_consent(id), other.has_consent(id))),
+        );
+        ConsentString {
+            version: self.version,
+            created: self.created,
+            last_updated: cmp::max(self.last_updated, other.last_updated),
+            cmp_id: self.cmp_id,
+            cmp_version: self.cmp_version,
+            consent_screen: self.consent_screen,
+            consent_language: self.consent_language,
+            vendor_list_version: self.vendor_list_version,
+            purposes_allowed,
+            max_vendor_id,
+            vendor_consents,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
     use *;
     #[test]
     fn it_works() {
@@ -248,7 +809,7 @@ mod tests {
 
         assert_eq!(consent_string.version, 1);
         let expected_time =
-            DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(1510082155, 400_000_000), Utc);
+            DateTime::<Utc>::from_timestamp(1510082155, 400_000_000).unwrap();
         assert_eq!(consent_string.created, expected_time);
         assert_eq!(consent_string.last_updated, expected_time);
         assert_eq!(consent_string.cmp_id, 7);
@@ -262,10 +823,282 @@ mod tests {
         );
         assert_eq!(consent_string.max_vendor_id, 2011);
         let mut consents = vec![true; 2012];
+        consents[0] = false;
         consents[9] = false;
         assert_eq!(consent_string.vendor_consents, consents);
     }
 
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            ConsentString::try_parse("BOEF!AyOEFEAyAHABDENAI4AAAB9vABAASA").unwrap_err(),
+            Error::InvalidCharacter {
+                character: '!',
+                position: 4
+            }
+        );
+        assert_eq!(
+            ConsentString::try_parse("BOEFEAyOEFEAyAHABDENAI4AAAB9vABA").unwrap_err(),
+            Error::Truncated("range entry")
+        );
+        // A consent language letter past `z`
+        assert_eq!(
+            ConsentString::try_parse("BOEFEAyOEFEAyAHABD_NAI4AAAB9vABAASA").unwrap_err(),
+            Error::Invalid("consent language")
+        );
+        // Vendor 9 excluded from a range of max vendor id 8
+        assert_eq!(
+            ConsentString::try_parse("BOEFEAyOEFEAyAHABDENAI4AAAAAjABAASA").unwrap_err(),
+            Error::Invalid("range entry")
+        );
+        let everyone = ConsentString::parse("BOEFEAyOEFEAyAHABDENAI4AAA___AAA").unwrap();
+        assert_eq!(everyone.max_vendor_id, 65535);
+        assert!(everyone.has_consent(65535));
+    }
+
+    #[test]
+    fn strict_and_lenient() {
+        let strict = ParseOptions::strict();
+        let lenient = ParseOptions::lenient();
+        let canonical = "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA";
+        let (gdpr, warnings) = ConsentString::parse_with(canonical, &strict).unwrap();
+        assert_eq!(warnings, vec![]);
+
+        let trailing = "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASAA";
+        assert_eq!(
+            ConsentString::parse_with(trailing, &strict).unwrap_err(),
+            Error::Nonconforming(Warning::TrailingData)
+        );
+        assert_eq!(
+            ConsentString::parse_with(trailing, &lenient).unwrap(),
+            (gdpr.clone(), vec![Warning::TrailingData])
+        );
+        let padding = "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASB";
+        assert_eq!(
+            ConsentString::parse_with(padding, &lenient).unwrap().1,
+            vec![Warning::NonZeroPadding]
+        );
+
+        let mut times = gdpr.clone();
+        times.created = DateTime::<Utc>::from_timestamp(0, 0).unwrap();
+        times.last_updated = times.created;
+        let (_, warnings) = ConsentString::parse_with(&times.encode().unwrap(), &lenient).unwrap();
+        assert_eq!(
+            warnings,
+            vec![
+                Warning::ZeroTimestamp("created"),
+                Warning::ZeroTimestamp("last updated"),
+            ]
+        );
+        times.created = gdpr.last_updated;
+        times.last_updated = gdpr.created - chrono::Duration::days(1);
+        assert_eq!(
+            ConsentString::parse_with(&times.encode().unwrap(), &strict).unwrap_err(),
+            Error::Nonconforming(Warning::UpdatedBeforeCreated)
+        );
+
+        // A bitfield of 12 vendors, cut off after vendor 7
+        let short = "BOEFEAyOEFEA8AHABDENAI4AAAAAxt";
+        assert_eq!(
+            ConsentString::try_parse(short).unwrap_err(),
+            Error::Truncated("vendor consents")
+        );
+        assert_eq!(ConsentString::parse(short), None);
+        assert_eq!(
+            ConsentString::parse_with(short, &strict).unwrap_err(),
+            Error::Nonconforming(Warning::ShortBitField)
+        );
+        let (gdpr, warnings) = ConsentString::parse_with(short, &lenient).unwrap();
+        assert_eq!(warnings, vec![Warning::ShortBitField]);
+        assert_eq!(gdpr.vendor_consents.len(), 13);
+        assert!(gdpr.has_consent(7) && !gdpr.has_consent(8));
+    }
+
+    #[test]
+    fn diff_and_merge() {
+        // cmp id 7, purposes 1-3, vendors 1-2 and 4-12 of 12
+        let a = ConsentString::parse("BOEFEAyOEFEA8AHABDENAI4AAAAAxt-A").unwrap();
+        // cmp id 9, purposes 2, 3 and 5, vendors 2-5 and 7-16 of 16
+        let b = ConsentString::parse("BOEFEAyOEFEA8AJABDENAIaAAAABA9_4").unwrap();
+        assert_eq!(a.diff(&a), vec![]);
+        assert_eq!(
+            a.diff(&b),
+            vec![
+                Change::CmpId(7, 9),
+                Change::MaxVendorId(12, 16),
+                Change::PurposesAdded(Purpose::Measurement.into()),
+                Change::PurposesRemoved(Purpose::StorageAndAccess.into()),
+                Change::VendorsGained(vec![3, 13, 14, 15, 16]),
+                Change::VendorsLost(vec![1]),
+            ]
+        );
+        let both = a.intersection(&b);
+        assert_eq!(both.purposes_allowed, Purpose::Personalization | Purpose::AdSelection);
+        assert_eq!(both.max_vendor_id, 16);
+        assert!(!both.has_consent(1) && both.has_consent(2) && !both.has_consent(13));
+        let either = a.union(&b);
+        assert!(either.has_consent(1) && either.has_consent(3) && either.has_consent(16));
+        assert!(!either.has_consent(6) && !either.has_consent(17));
+        assert_eq!(
+            either.diff(&b),
+            vec![
+                Change::CmpId(7, 9),
+                Change::PurposesRemoved(Purpose::StorageAndAccess.into()),
+                Change::VendorsLost(vec![1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn encode() {
+        for input in &[
+            "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA",
+            "BOEFEAyOEFEA8AHABDENAI4AAAAAxt-A",
+            "BOEFEAyOEFEA8AJABDENAIaAAAABA9_4",
+        ] {
+            let consent_string = ConsentString::parse(input).unwrap();
+            assert_eq!(&consent_string.encode().unwrap(), input);
+        }
+        let mut consent_string = ConsentString::parse("BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA").unwrap();
+        consent_string.consent_language = ['e', 'N'];
+        assert_eq!(consent_string.encode(), Err(Error::Invalid("consent language")));
+        consent_string.consent_language = ['e', 'n'];
+        consent_string.cmp_id = 5000;
+        assert_eq!(consent_string.encode(), Err(Error::Invalid("CMP id")));
+        consent_string.cmp_id = 7;
+        consent_string.version = 64;
+        assert_eq!(consent_string.encode(), Err(Error::Invalid("version")));
+        consent_string.version = 1;
+        consent_string.last_updated = DateTime::<Utc>::from_timestamp(1 << 33, 0).unwrap();
+        assert_eq!(consent_string.encode(), Err(Error::Invalid("last updated")));
+    }
+
+    #[cfg(feature = "generate")]
+    #[test]
+    fn generate() {
+        use generate::Config;
+        for &encoding in &[VendorEncoding::BitField, VendorEncoding::Range] {
+            let config = Config {
+                encoding,
+                ..Config::default()
+            };
+            let generate = |config| Generator::new(config, 7).unwrap().take(20).collect();
+            let strings: Vec<String> = generate(config.clone());
+            assert_eq!(strings, generate(config));
+            for string in strings {
+                let consent_string = ConsentString::parse(&string).unwrap();
+                assert_eq!(consent_string.encode_with(encoding).unwrap(), string);
+            }
+        }
+        let config = Config {
+            languages: vec![['e', 'n'], ['F', 'R']],
+            ..Config::default()
+        };
+        assert_eq!(
+            Generator::new(config, 7).unwrap_err(),
+            Error::Invalid("consent language")
+        );
+    }
+
+    /// Any consent string that can be encoded. Vendor consents come in runs, so that range
+    /// encodings are worth choosing.
+    fn consent_string() -> impl Strategy<Value = ConsentString> {
+        let header = (
+            0u8..1 << 6,
+            0u64..1 << 36,
+            0u64..1 << 36,
+            0u16..1 << 12,
+            0u16..1 << 12,
+            0u8..1 << 6,
+            (0u8..26, 0u8..26),
+            0u16..1 << 12,
+            0u32..1 << 24,
+        );
+        let runs = prop::collection::vec((any::<bool>(), 1usize..300), 0..40);
+        (header, runs).prop_map(|(header, runs)| {
+            let (version, created, last_updated, cmp_id, cmp_version, consent_screen) =
+                (header.0, header.1, header.2, header.3, header.4, header.5);
+            let mut vendor_consents = vec![false];
+            for (consent, len) in runs {
+                vendor_consents.extend(::std::iter::repeat_n(consent, len));
+            }
+            ConsentString {
+                version,
+                created: time(created).unwrap(),
+                last_updated: time(last_updated).unwrap(),
+                cmp_id,
+                cmp_version,
+                consent_screen,
+                consent_language: [
+                    (b'a' + (header.6).0) as char,
+                    (b'a' + (header.6).1) as char,
+                ],
+                vendor_list_version: header.7,
+                purposes_allowed: Purposes::from_raw(header.8),
+                max_vendor_id: (vendor_consents.len() - 1) as u16,
+                vendor_consents,
+            }
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(consent_string in consent_string()) {
+            let encodings = [
+                VendorEncoding::Shortest,
+                VendorEncoding::BitField,
+                VendorEncoding::Range,
+            ];
+            for &encoding in &encodings {
+                let encoded = consent_string.encode_with(encoding).unwrap();
+                prop_assert_eq!(ConsentString::try_parse(&encoded), Ok(consent_string.clone()));
+                let (_, warnings) = ConsentString::parse_with(&encoded, &ParseOptions::lenient())
+                    .unwrap();
+                let timestamps_only = warnings.iter().all(|warning| {
+                    matches!(warning, Warning::ZeroTimestamp(_) | Warning::UpdatedBeforeCreated)
+                });
+                prop_assert!(timestamps_only, "{:?}", warnings);
+            }
+            let shortest = consent_string.encode().unwrap().len();
+            for &encoding in &encodings {
+                prop_assert!(consent_string.encode_with(encoding).unwrap().len() >= shortest);
+            }
+        }
+
+        #[test]
+        fn take_every_width(value in any::<u64>(), prefix in 0u8..6) {
+            // Start partway through a character, so that every alignment is covered
+            let mut be = BitEncoder::default();
+            be.put(0, prefix);
+            for n in 1..=64 {
+                be.put(value, n);
+            }
+            let encoded = be.finish();
+            let mut bd = BitDecoder::new(encoded.chars());
+            prop_assert_eq!(bd.take(prefix), Some(0));
+            for n in 1..=64u8 {
+                let mask = if n == 64 { !0 } else { (1 << n) - 1 };
+                prop_assert_eq!(bd.take(n), Some(value & mask), "width {}", n);
+            }
+        }
+
+        #[test]
+        fn take_mixed_widths(fields in prop::collection::vec((1u8..=64, any::<u64>()), 0..50)) {
+            let mut be = BitEncoder::default();
+            for &(n, value) in &fields {
+                be.put(value, n);
+            }
+            let len: usize = fields.iter().map(|&(n, _)| n as usize).sum();
+            let encoded = be.finish();
+            prop_assert!(encoded.len() * 6 >= len && encoded.len() * 6 < len + 8 + 6);
+            let mut bd = BitDecoder::new(encoded.chars());
+            for &(n, value) in &fields {
+                let mask = if n == 64 { !0 } else { (1 << n) - 1 };
+                prop_assert_eq!(bd.take(n), Some(value & mask));
+            }
+        }
+    }
+
     #[test]
     fn thingie_iter() {
         let words: Vec<char> = vec!['c', 'c'];
@@ -0,0 +1,73 @@
+use serde_json;
+use std::io::Read;
+
+/// The IAB Global Vendor List, as published at `https://vendorlist.consensu.org/vendorlist.json`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VendorList {
+    pub vendor_list_version: u16,
+    pub last_updated: Option<String>,
+    #[serde(default)]
+    pub purposes: Vec<Purpose>,
+    #[serde(default)]
+    pub features: Vec<Feature>,
+    pub vendors: Vec<Vendor>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Purpose {
+    pub id: u8,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Feature {
+    pub id: u8,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Vendor {
+    pub id: u16,
+    pub name: String,
+    pub policy_url: Option<String>,
+    #[serde(default)]
+    pub purpose_ids: Vec<u8>,
+    #[serde(default)]
+    pub leg_int_purpose_ids: Vec<u8>,
+    #[serde(default)]
+    pub feature_ids: Vec<u8>,
+    pub deleted_date: Option<String>,
+}
+
+impl VendorList {
+    pub fn from_reader<R: Read>(reader: R) -> serde_json::Result<VendorList> {
+        serde_json::from_reader(reader)
+    }
+
+    pub fn vendor(&self, id: u16) -> Option<&Vendor> {
+        self.vendors.iter().find(|vendor| vendor.id == id)
+    }
+
+    /// Finds vendors by name, ignoring case. An exact match is preferred; failing that, every
+    /// vendor whose name contains `name` is returned.
+    pub fn vendors_named(&self, name: &str) -> Vec<&Vendor> {
+        let name = name.to_lowercase();
+        let exact: Vec<_> = self.vendors
+            .iter()
+            .filter(|vendor| vendor.name.to_lowercase() == name)
+            .collect();
+        if !exact.is_empty() {
+            return exact;
+        }
+        self.vendors
+            .iter()
+            .filter(|vendor| vendor.name.to_lowercase().contains(&name))
+            .collect()
+    }
+}